@@ -0,0 +1,247 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+use vrchatapi::apis::configuration::Configuration;
+
+use crate::blocklist::history;
+use crate::config::CONFIG;
+use crate::events::schema::PendingBanData;
+use crate::vrchat::{audit, ban_approval, evidence};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8788";
+
+struct Server {
+    auth_config: Configuration,
+}
+
+fn authorized(headers: &HeaderMap) -> bool {
+    let Some(token) = &CONFIG.api.token else {
+        return false;
+    };
+
+    headers
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| given == token)
+}
+
+fn error_response(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn pending_bans(headers: HeaderMap) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let pending = ban_approval::pending()
+        .await
+        .iter()
+        .map(PendingBanData::from)
+        .collect::<Vec<_>>();
+
+    Json(pending).into_response()
+}
+
+async fn approve_ban(
+    State(server): State<Arc<Server>>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match ban_approval::approve(&server.auth_config, id, "local API").await {
+        Ok(ban) => Json(PendingBanData::from(&ban)).into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, format!("{e:#}")),
+    }
+}
+
+async fn discard_ban(Path(id): Path<u64>, headers: HeaderMap) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match ban_approval::discard(id, "local API").await {
+        Ok(ban) => Json(PendingBanData::from(&ban)).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, format!("{e:#}")),
+    }
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    limit: Option<usize>,
+}
+
+async fn audit_log(Query(query): Query<AuditQuery>, headers: HeaderMap) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let entries = audit::entries().await;
+    let skip = query
+        .limit
+        .map_or(0, |limit| entries.len().saturating_sub(limit));
+
+    Json(&entries[skip..]).into_response()
+}
+
+async fn evidence_bundle(Path(id): Path<u64>, headers: HeaderMap) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match evidence::get(id).await {
+        Some(bundle) => Json(bundle).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, format!("No evidence #{id}")),
+    }
+}
+
+async fn undo_action(
+    State(server): State<Arc<Server>>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match audit::undo(&server.auth_config, id, "local API").await {
+        Ok(entry) => Json(entry).into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, format!("{e:#}")),
+    }
+}
+
+#[derive(Deserialize)]
+struct UndoRule {
+    rule: String,
+    since: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+}
+
+async fn undo_rule(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Json(request): Json<UndoRule>,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let until = request.until.unwrap_or_else(Utc::now);
+    let (undone, failed) = audit::undo_rule(
+        &server.auth_config,
+        &request.rule,
+        request.since,
+        until,
+        "local API",
+    )
+    .await;
+
+    let failed = failed
+        .into_iter()
+        .map(|(id, e)| json!({ "id": id, "error": format!("{e:#}") }))
+        .collect::<Vec<_>>();
+
+    Json(json!({ "undone": undone, "failed": failed })).into_response()
+}
+
+async fn blocklist_history(headers: HeaderMap) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    Json(history::versions().await).into_response()
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: u64,
+    to: Option<u64>,
+}
+
+async fn blocklist_diff(Query(query): Query<DiffQuery>, headers: HeaderMap) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match history::diff(query.from, query.to).await {
+        Ok(change) => Json(change).into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, format!("{e:#}")),
+    }
+}
+
+#[derive(Deserialize)]
+struct Rollback {
+    version: u64,
+    reason: Option<String>,
+}
+
+async fn blocklist_rollback(headers: HeaderMap, Json(request): Json<Rollback>) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let reason = request
+        .reason
+        .unwrap_or_else(|| format!("Rolled back to version {}", request.version));
+
+    match history::rollback(request.version, "local API", &reason).await {
+        Ok(version) => Json(version).into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, format!("{e:#}")),
+    }
+}
+
+pub fn init(auth_config: &Configuration) {
+    let address = CONFIG
+        .api
+        .listen
+        .clone()
+        .unwrap_or(DEFAULT_LISTEN.to_string());
+
+    // Local processes and web pages (through CSRF) can reach it, so it never runs open.
+    if CONFIG.api.token.as_deref().is_none_or(str::is_empty) {
+        error!("Local API disabled: set api.token");
+        return;
+    }
+
+    let server = Arc::new(Server {
+        auth_config: auth_config.clone(),
+    });
+    let router = Router::new()
+        .route("/bans/pending", get(pending_bans))
+        .route("/bans/pending/{id}/approve", post(approve_ban))
+        .route("/bans/pending/{id}/discard", post(discard_ban))
+        .route("/audit", get(audit_log))
+        .route("/audit/undo", post(undo_rule))
+        .route("/audit/{id}/undo", post(undo_action))
+        .route("/evidence/{id}", get(evidence_bundle))
+        .route("/blocklist/history", get(blocklist_history))
+        .route("/blocklist/diff", get(blocklist_diff))
+        .route("/blocklist/rollback", post(blocklist_rollback))
+        .with_state(server);
+
+    tokio::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start local API on {}: {}", address, e);
+                return;
+            }
+        };
+
+        info!("Local API listening on {}", address);
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("Local API failed: {}", e);
+        }
+    });
+}
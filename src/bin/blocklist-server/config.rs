@@ -0,0 +1,32 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Moderator {
+    /// The group they moderate for, kept with each of their votes.
+    pub group: Option<String>,
+    /// Their `vrc-manager blocklist keygen` public key, in hex.
+    pub public_key: String,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub listen: Option<String>,
+    /// Published as the list's `name`.
+    pub name: Option<String>,
+    pub data_dir: Option<String>,
+    /// Signs the merged list, created on first start when missing.
+    pub signing_key: Option<String>,
+    /// Votes to block needed before an entry is published.
+    pub min_votes: usize,
+    pub moderators: HashMap<String, Moderator>,
+}
+
+pub fn load(path: &str) -> Result<Config> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?;
+    toml::from_str(&contents).with_context(|| format!("Failed to parse {path}"))
+}
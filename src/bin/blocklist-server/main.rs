@@ -0,0 +1,217 @@
+//! Serves a blocklist merged from the votes of trusted moderators' vrc-manager instances.
+
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use ed25519_dalek::SigningKey;
+use serde::Serialize;
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+use vrc_manager::blocklist::{Submission, Vote, signing};
+
+use crate::config::Config;
+use crate::store::{NotStored, Record, Store};
+
+mod config;
+mod store;
+
+const DEFAULT_CONFIG: &str = "blocklist-server.toml";
+const DEFAULT_LISTEN: &str = "0.0.0.0:8789";
+
+struct Server {
+    config: Config,
+    store: Mutex<Store>,
+}
+
+fn error_response(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn blocklist(State(server): State<Arc<Server>>, headers: HeaderMap) -> Response {
+    let store = server.store.lock().await;
+    let published = &store.published;
+
+    let unchanged = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|etag| etag == published.etag);
+    if unchanged {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, published.etag.clone())]).into_response();
+    }
+
+    (
+        [
+            (CONTENT_TYPE, "application/json".to_string()),
+            (ETAG, published.etag.clone()),
+        ],
+        published.body.clone(),
+    )
+        .into_response()
+}
+
+async fn signature(State(server): State<Arc<Server>>) -> Response {
+    match &server.store.lock().await.published.signature {
+        Some(signature) => signature.clone().into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[derive(Serialize)]
+struct RecordSummary<'a> {
+    #[serde(flatten)]
+    record: &'a Record,
+    blocks: usize,
+    unblocks: usize,
+    listed: bool,
+}
+
+impl<'a> RecordSummary<'a> {
+    fn new(record: &'a Record, listed: bool) -> Self {
+        Self {
+            record,
+            blocks: record.count(Vote::Block),
+            unblocks: record.count(Vote::Unblock),
+            listed,
+        }
+    }
+}
+
+/// Every entry ever submitted, with its provenance and votes.
+async fn entries(State(server): State<Arc<Server>>) -> Response {
+    let store = server.store.lock().await;
+    let records = store
+        .records()
+        .map(|(record, listed)| RecordSummary::new(record, listed))
+        .collect::<Vec<_>>();
+
+    Json(records).into_response()
+}
+
+async fn submit(State(server): State<Arc<Server>>, headers: HeaderMap, body: Bytes) -> Response {
+    let Some(signature) = headers
+        .get(signing::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return error_response(
+            StatusCode::UNAUTHORIZED,
+            format!("Missing {} header", signing::SIGNATURE_HEADER),
+        );
+    };
+
+    let submission: Submission = match serde_json::from_slice(&body) {
+        Ok(submission) => submission,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, format!("Invalid body: {e}")),
+    };
+
+    let Some(moderator) = server.config.moderators.get(&submission.moderator) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Unknown moderator".to_string());
+    };
+
+    if let Err(e) = signing::verify(&moderator.public_key, &body, signature) {
+        warn!(
+            "Rejected a submission claiming to be from {}: {:#}",
+            submission.moderator, e
+        );
+        return error_response(StatusCode::UNAUTHORIZED, format!("{e:#}"));
+    }
+
+    let mut store = server.store.lock().await;
+    match store.submit(submission, moderator.group.clone()) {
+        Ok(record) => {
+            let listed = record.is_listed(server.config.min_votes);
+            Json(RecordSummary::new(record, listed)).into_response()
+        }
+        Err(e) if e.is::<NotStored>() => {
+            error!("{:#}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+        Err(e) => error_response(StatusCode::BAD_REQUEST, format!("{e:#}")),
+    }
+}
+
+/// Loads the key the merged list is signed with, creating it on first start.
+fn signing_key(path: &Path) -> Result<SigningKey> {
+    if path.exists() {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        return signing::decode_signing_key(&contents);
+    }
+
+    let key = signing::generate_key();
+    signing::write_signing_key(path, &key)?;
+    info!("Created a signing key in {}", path.display());
+
+    Ok(key)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let config_path = std::env::args()
+        .nth(1)
+        .unwrap_or(DEFAULT_CONFIG.to_string());
+    let config = config::load(&config_path)?;
+
+    if config.moderators.is_empty() {
+        warn!("No moderators configured, nobody can submit entries");
+    }
+
+    let signing_key = match &config.signing_key {
+        Some(path) => {
+            let key = signing_key(Path::new(path))?;
+            info!(
+                "Signing the list, subscribers trust this public key: {}",
+                signing::public_key(&key)
+            );
+            Some(key)
+        }
+        None => {
+            warn!("No signing_key set, the list is served unsigned");
+            None
+        }
+    };
+
+    let data_dir = PathBuf::from(
+        config
+            .data_dir
+            .as_deref()
+            .unwrap_or("blocklist-server-data"),
+    );
+    let store = Store::open(data_dir, config.name.clone(), config.min_votes, signing_key)?;
+    info!(
+        "Serving {} entries from {} moderators",
+        store.published.entries.len(),
+        config.moderators.len()
+    );
+
+    let address = config.listen.clone().unwrap_or(DEFAULT_LISTEN.to_string());
+    let server = Arc::new(Server {
+        config,
+        store: Mutex::new(store),
+    });
+    let router = Router::new()
+        .route("/blocklist.json", get(blocklist))
+        .route("/blocklist.json.sig", get(signature))
+        .route("/entries", get(entries))
+        .route("/submissions", post(submit))
+        .with_state(server);
+
+    let listener = tokio::net::TcpListener::bind(&address)
+        .await
+        .with_context(|| format!("Failed to listen on {address}"))?;
+
+    info!("Blocklist server listening on {}", address);
+    axum::serve(listener, router).await?;
+
+    Ok(())
+}
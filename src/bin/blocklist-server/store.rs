@@ -0,0 +1,246 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, TimeDelta, Utc};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+use tracing::info;
+use vrc_manager::blocklist::{BlocklistEntry, BlocklistFile, Submission, Vote, signing};
+
+const STATE_FILE: &str = "state.json";
+/// How far a submission's timestamp may be from the server's clock.
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(5);
+
+/// The submission was fine, but couldn't be stored. Nothing about it was kept.
+#[derive(Debug, Error)]
+#[error("Failed to store the submission")]
+pub struct NotStored;
+
+/// One moderator's current vote on an entry.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Ballot {
+    pub moderator: String,
+    pub group: Option<String>,
+    pub vote: Vote,
+    pub reason: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+/// An entry with who first submitted it and everyone's votes on it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Record {
+    pub id: String,
+    pub submitted_by: String,
+    pub submitted_at: DateTime<Utc>,
+    pub ballots: Vec<Ballot>,
+}
+
+impl Record {
+    pub fn count(&self, vote: Vote) -> usize {
+        self.ballots
+            .iter()
+            .filter(|ballot| ballot.vote == vote)
+            .count()
+    }
+
+    pub fn is_listed(&self, min_votes: usize) -> bool {
+        let blocks = self.count(Vote::Block);
+        blocks >= min_votes.max(1) && blocks > self.count(Vote::Unblock)
+    }
+
+    /// The reason given with the earliest vote to block.
+    fn reason(&self) -> Option<String> {
+        self.ballots
+            .iter()
+            .filter(|ballot| ballot.vote == Vote::Block)
+            .min_by_key(|ballot| ballot.submitted_at)
+            .and_then(|ballot| ballot.reason.clone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct State {
+    records: BTreeMap<String, Record>,
+    /// The newest accepted submission per moderator, so none can be replayed.
+    last_submission: HashMap<String, DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// The merged list as served, signed once per change.
+#[derive(Default, Clone)]
+pub struct Published {
+    pub entries: Vec<BlocklistEntry>,
+    pub body: Vec<u8>,
+    pub signature: Option<String>,
+    pub etag: String,
+}
+
+pub struct Store {
+    path: PathBuf,
+    state: State,
+    name: Option<String>,
+    min_votes: usize,
+    signing_key: Option<SigningKey>,
+    pub published: Published,
+}
+
+impl Store {
+    pub fn open(
+        data_dir: PathBuf,
+        name: Option<String>,
+        min_votes: usize,
+        signing_key: Option<SigningKey>,
+    ) -> Result<Self> {
+        let path = data_dir.join(STATE_FILE);
+        let state = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+
+        let mut store = Self {
+            path,
+            state,
+            name,
+            min_votes,
+            signing_key,
+            published: Published::default(),
+        };
+        store.publish()?;
+
+        Ok(store)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = (&Record, bool)> {
+        self.state
+            .records
+            .values()
+            .map(|record| (record, record.is_listed(self.min_votes)))
+    }
+
+    /// Rebuilds the served list, moving `updated_at` only when its entries changed.
+    fn publish(&mut self) -> Result<()> {
+        let entries = self
+            .state
+            .records
+            .values()
+            .filter(|record| record.is_listed(self.min_votes))
+            .map(|record| BlocklistEntry {
+                id: record.id.clone(),
+                reason: record.reason(),
+            })
+            .collect::<Vec<_>>();
+
+        if !self.published.body.is_empty() && entries == self.published.entries {
+            return Ok(());
+        }
+        if !self.published.body.is_empty() || self.state.updated_at.is_none() {
+            self.state.updated_at = Some(Utc::now());
+        }
+
+        let list = BlocklistFile {
+            name: self.name.clone(),
+            updated_at: self.state.updated_at,
+            entries: entries.clone(),
+        };
+        let body = serde_json::to_vec_pretty(&list).context("Failed to serialize the list")?;
+
+        self.published = Published {
+            entries,
+            signature: self
+                .signing_key
+                .as_ref()
+                .map(|key| signing::sign(key, &body)),
+            etag: format!("\"{}\"", hex::encode(Sha256::digest(&body))),
+            body,
+        };
+
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).context("Failed to create data directory")?;
+        }
+
+        let contents =
+            serde_json::to_string_pretty(&self.state).context("Failed to serialize state")?;
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, contents).context("Failed to write state")?;
+        fs::rename(&tmp_path, &self.path).context("Failed to replace state")
+    }
+
+    /// Records a verified submission, replacing the moderator's earlier vote on the entry.
+    /// When it can't be saved, the store is left as it was and the error is `NotStored`.
+    pub fn submit(&mut self, submission: Submission, group: Option<String>) -> Result<&Record> {
+        let id = submission.id.trim().to_string();
+        if id.is_empty() || id.contains(char::is_whitespace) {
+            bail!("Invalid entry ID");
+        }
+
+        if (Utc::now() - submission.submitted_at).abs() > MAX_CLOCK_SKEW {
+            bail!("Submission timestamp is too far from the server's clock");
+        }
+        if self
+            .state
+            .last_submission
+            .get(&submission.moderator)
+            .is_some_and(|last| submission.submitted_at <= *last)
+        {
+            bail!("Submission is not newer than the last one from this moderator");
+        }
+
+        if submission.vote == Vote::Unblock && !self.state.records.contains_key(&id) {
+            bail!("{id} was never submitted");
+        }
+
+        // Restored if saving fails, so a vote the moderator was told about is never lost.
+        let previous = (self.state.clone(), self.published.clone());
+
+        let record = self
+            .state
+            .records
+            .entry(id.clone())
+            .or_insert_with(|| Record {
+                id: id.clone(),
+                submitted_by: submission.moderator.clone(),
+                submitted_at: submission.submitted_at,
+                ballots: Vec::new(),
+            });
+
+        record
+            .ballots
+            .retain(|ballot| ballot.moderator != submission.moderator);
+        record.ballots.push(Ballot {
+            moderator: submission.moderator.clone(),
+            group,
+            vote: submission.vote,
+            reason: submission.reason,
+            submitted_at: submission.submitted_at,
+        });
+
+        info!(
+            "{} voted to {:?} {} ({} block, {} unblock)",
+            submission.moderator,
+            submission.vote,
+            id,
+            record.count(Vote::Block),
+            record.count(Vote::Unblock)
+        );
+
+        self.state
+            .last_submission
+            .insert(submission.moderator, submission.submitted_at);
+
+        if let Err(e) = self.publish().and_then(|()| self.save()) {
+            (self.state, self.published) = previous;
+            return Err(e.context(NotStored));
+        }
+
+        Ok(&self.state.records[&id])
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{BlocklistEntry, BlocklistFile, Submission, Vote, parse_lines, signing};
+use crate::config::CONFIG;
+use crate::storage;
+use crate::vrchat::auto_ban;
+
+const DEFAULT_KEY_FILE: &str = "blocklist_signing.key";
+const DEFAULT_OUTPUT: &str = "published/blocklist.json";
+
+fn key_file() -> PathBuf {
+    match &CONFIG.blocklists.signing_key {
+        Some(path) => PathBuf::from(path),
+        None => storage::path(DEFAULT_KEY_FILE),
+    }
+}
+
+fn load_signing_key() -> Result<SigningKey> {
+    let path = key_file();
+    let contents = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Failed to read {}, create one with `vrc-manager blocklist keygen`",
+            path.display()
+        )
+    })?;
+
+    signing::decode_signing_key(&contents)
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn keygen() -> Result<()> {
+    let path = key_file();
+
+    if path.exists() {
+        bail!("{} already exists, not overwriting it", path.display());
+    }
+
+    let signing_key = signing::generate_key();
+    signing::write_signing_key(&path, &signing_key)?;
+
+    println!(
+        "Saved the signing key to {}, keep it private",
+        path.display()
+    );
+    println!("Public key: {}", signing::public_key(&signing_key));
+    Ok(())
+}
+
+/// Signs the local avatar list (or `input`) into a JSON list with a `.sig` file next to it.
+fn sign(input: Option<&str>, output: Option<&str>) -> Result<()> {
+    let signing_key = load_signing_key()?;
+
+    let input = input.map_or_else(auto_ban::avatar_file, str::to_string);
+    let output = output.map_or_else(|| storage::path(DEFAULT_OUTPUT), PathBuf::from);
+    let contents = fs::read_to_string(&input).with_context(|| format!("Failed to read {input}"))?;
+
+    let list = BlocklistFile {
+        name: CONFIG.group_id.clone(),
+        updated_at: Some(Utc::now()),
+        entries: parse_lines(&contents)
+            .into_iter()
+            .map(|id| BlocklistEntry { id, reason: None })
+            .collect(),
+    };
+
+    let body = serde_json::to_vec_pretty(&list).context("Failed to serialize the list")?;
+
+    let mut signature_path = output.clone().into_os_string();
+    signature_path.push(".sig");
+    let signature_path = PathBuf::from(signature_path);
+
+    write(&output, &body)?;
+    write(
+        &signature_path,
+        signing::sign(&signing_key, &body).as_bytes(),
+    )?;
+
+    println!(
+        "Signed {} entries into {} and {}",
+        list.entries.len(),
+        output.display(),
+        signature_path.display()
+    );
+    println!("Upload both files where subscribers can fetch them");
+    Ok(())
+}
+
+/// Sends a signed vote to the blocklist server in `server_url`.
+async fn submit(vote: Vote, id: &str, reason: &[&str]) -> Result<()> {
+    let settings = &CONFIG.blocklists;
+    let server_url = settings
+        .server_url
+        .as_deref()
+        .context("blocklists.server_url config variable is not set")?;
+    let moderator = settings
+        .moderator
+        .clone()
+        .context("blocklists.moderator config variable is not set")?;
+
+    let submission = Submission {
+        moderator,
+        id: id.to_string(),
+        vote,
+        reason: (!reason.is_empty()).then(|| reason.join(" ")),
+        submitted_at: Utc::now(),
+    };
+
+    let body = serde_json::to_vec(&submission).context("Failed to serialize the submission")?;
+    let signature = signing::sign(&load_signing_key()?, &body);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/submissions", server_url.trim_end_matches('/')))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(signing::SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await
+        .context("Request failed")?;
+
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        bail!("Server refused the submission ({status}): {text}");
+    }
+
+    println!("{text}");
+    Ok(())
+}
+
+/// Handles `vrc-manager blocklist <command>`.
+pub async fn run(args: &[String]) -> Result<()> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        ["keygen"] => keygen(),
+        ["sign", rest @ ..] if rest.len() <= 2 => sign(rest.first().copied(), rest.get(1).copied()),
+        ["submit", id, reason @ ..] => submit(Vote::Block, id, reason).await,
+        ["retract", id, reason @ ..] => submit(Vote::Unblock, id, reason).await,
+        _ => {
+            println!("Usage:");
+            println!("  vrc-manager blocklist keygen                  create a signing key");
+            println!("  vrc-manager blocklist sign [input] [output]   sign a list for publishing");
+            println!(
+                "  vrc-manager blocklist submit <id> [reason]    vote to block an entry on the server"
+            );
+            println!(
+                "  vrc-manager blocklist retract <id> [reason]   vote to unblock an entry on the server"
+            );
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Blocklist formats shared by `auto_ban`, remote subscriptions and `blocklist-server`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlocklistEntry {
+    /// An avatar image file (`file_...`), avatar (`avtr_...`) or avatar author (`usr_...`) ID.
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A published blocklist. Plain text lists with one ID per line are read into this too.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BlocklistFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    pub entries: Vec<BlocklistEntry>,
+}
+
+impl BlocklistFile {
+    /// Parses a JSON blocklist, or a plain one with an ID per line and `#` comments.
+    pub fn parse(body: &str) -> Result<Self> {
+        if body.trim_start().starts_with('{') {
+            return serde_json::from_str(body).context("Invalid JSON blocklist");
+        }
+
+        let entries = parse_lines(body)
+            .into_iter()
+            .map(|id| BlocklistEntry { id, reason: None })
+            .collect();
+
+        Ok(Self {
+            entries,
+            ..Default::default()
+        })
+    }
+}
+
+/// Reads a one-entry-per-line list, ignoring blank lines and `#` comments.
+pub fn parse_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Entries that appeared in and disappeared from a list between two versions.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BlocklistChange {
+    pub source: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl BlocklistChange {
+    pub fn between(source: &str, old: &HashSet<String>, new: &HashSet<String>) -> Self {
+        let mut added = new.difference(old).cloned().collect::<Vec<_>>();
+        let mut removed = old.difference(new).cloned().collect::<Vec<_>>();
+        added.sort();
+        removed.sort();
+
+        Self {
+            source: source.to_string(),
+            added,
+            removed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Vote {
+    Block,
+    Unblock,
+}
+
+/// A moderator's vote on one entry, sent to a blocklist server signed with their key.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Submission {
+    pub moderator: String,
+    pub id: String,
+    pub vote: Vote,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+}
@@ -0,0 +1,399 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use super::{BlocklistChange, parse_lines};
+use crate::events::{AppEvent, EVENT_BUS};
+use crate::storage;
+use crate::vrchat::{auto_ban, util};
+
+const HISTORY_FILE: &str = "blocklist_history.jsonl";
+/// How often hand edits of the avatar file are picked up.
+const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+/// Author of versions found in the file rather than made through the manager.
+const FILE_EDIT: &str = "file edit";
+
+/// One change to the avatar blocklist. Version 0 is the empty list.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlocklistVersion {
+    pub version: u64,
+    pub author: String,
+    pub reason: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    /// Set when this version restored an earlier one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_to: Option<u64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+static HISTORY: Lazy<Mutex<Vec<BlocklistVersion>>> =
+    Lazy::new(|| Mutex::new(storage::load_lines(HISTORY_FILE)));
+
+fn entries_at(history: &[BlocklistVersion], version: u64) -> BTreeSet<String> {
+    let mut entries = BTreeSet::new();
+
+    for change in history
+        .iter()
+        .take_while(|change| change.version <= version)
+    {
+        for id in &change.removed {
+            entries.remove(id);
+        }
+        entries.extend(change.added.iter().cloned());
+    }
+
+    entries
+}
+
+fn latest(history: &[BlocklistVersion]) -> u64 {
+    history.last().map_or(0, |change| change.version)
+}
+
+fn read_file() -> Result<String> {
+    match util::read_lossy(&auto_ban::avatar_file()) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e).context("Failed to read avatar list"),
+    }
+}
+
+/// Appends a version taking the list from the latest one to `entries`, if anything changed.
+/// It's only kept in memory once it's on disk, so version numbers survive a restart.
+fn record(
+    history: &mut Vec<BlocklistVersion>,
+    entries: &BTreeSet<String>,
+    author: &str,
+    reason: &str,
+    rollback_to: Option<u64>,
+) -> Result<Option<BlocklistVersion>> {
+    let previous = entries_at(history, latest(history));
+    let added = entries.difference(&previous).cloned().collect::<Vec<_>>();
+    let removed = previous.difference(entries).cloned().collect::<Vec<_>>();
+
+    if added.is_empty() && removed.is_empty() {
+        return Ok(None);
+    }
+
+    let version = BlocklistVersion {
+        version: latest(history) + 1,
+        author: author.to_string(),
+        reason: reason.to_string(),
+        added,
+        removed,
+        rollback_to,
+        timestamp: Utc::now(),
+    };
+
+    storage::append(HISTORY_FILE, &version).context("Failed to save blocklist history")?;
+    history.push(version.clone());
+
+    Ok(Some(version))
+}
+
+/// Records edits made to the file by hand since the latest version.
+fn sync_locked(history: &mut Vec<BlocklistVersion>) -> Result<()> {
+    let entries = parse_lines(&read_file()?).into_iter().collect();
+    let reason = if history.is_empty() {
+        "Initial version"
+    } else {
+        "Edited outside vrc-manager"
+    };
+
+    if let Some(version) = record(history, &entries, FILE_EDIT, reason, None)? {
+        info!(
+            "Recorded blocklist version {}: {} added, {} removed",
+            version.version,
+            version.added.len(),
+            version.removed.len()
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn versions() -> Vec<BlocklistVersion> {
+    let mut history = HISTORY.lock().await;
+
+    if let Err(e) = sync_locked(&mut history) {
+        error!("Failed to sync blocklist history: {:#}", e);
+    }
+
+    history.clone()
+}
+
+/// Changes the avatar file, then saves the version describing the change. If the version
+/// can't be saved the file is put back, or the next sync would credit the change to a file edit.
+fn apply<T>(
+    path: &Path,
+    change: impl FnOnce() -> Result<()>,
+    save: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let previous = match fs::read(path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e).context("Failed to read avatar list"),
+    };
+
+    change()?;
+
+    save().inspect_err(|_| {
+        let restored = match &previous {
+            Some(contents) => storage::replace(path, contents),
+            None => fs::remove_file(path).context("Failed to remove avatar list"),
+        };
+
+        if let Err(e) = restored {
+            error!("Failed to undo unrecorded avatar list change: {:#}", e);
+        }
+    })
+}
+
+/// Adds an entry to the avatar list. Returns false if it was already listed.
+pub async fn add(id: &str, author: &str, reason: &str) -> Result<bool> {
+    let mut history = HISTORY.lock().await;
+    sync_locked(&mut history)?;
+
+    let mut entries = entries_at(&history, latest(&history));
+    if !entries.insert(id.to_string()) {
+        return Ok(false);
+    }
+
+    let path = auto_ban::avatar_file();
+    apply(
+        Path::new(&path),
+        || util::append_line(&path, id).context("Failed to update avatar list"),
+        || record(&mut history, &entries, author, reason, None),
+    )?;
+
+    Ok(true)
+}
+
+/// Removes an entry from the avatar list. Returns false if it wasn't listed.
+pub async fn remove(id: &str, author: &str, reason: &str) -> Result<bool> {
+    let mut history = HISTORY.lock().await;
+    sync_locked(&mut history)?;
+
+    let mut entries = entries_at(&history, latest(&history));
+    if !entries.remove(id) {
+        return Ok(false);
+    }
+
+    apply(
+        Path::new(&auto_ban::avatar_file()),
+        || rewrite(&HashSet::from([id.to_string()]), &[]),
+        || record(&mut history, &entries, author, reason, None),
+    )?;
+
+    Ok(true)
+}
+
+/// Drops the lines of `removed` entries and appends `added` ones, keeping comments in place.
+fn rewrite(removed: &HashSet<String>, added: &[String]) -> Result<()> {
+    let mut lines = read_file()?
+        .lines()
+        .filter(|line| {
+            parse_lines(line)
+                .first()
+                .is_none_or(|id| !removed.contains(id))
+        })
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    lines.extend(added.iter().cloned());
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+
+    storage::replace(Path::new(&auto_ban::avatar_file()), &contents)
+        .context("Failed to write avatar list")
+}
+
+/// The entries added and removed going from version `from` to `to`.
+pub async fn diff(from: u64, to: Option<u64>) -> Result<BlocklistChange> {
+    let mut history = HISTORY.lock().await;
+    sync_locked(&mut history)?;
+
+    let to = to.unwrap_or(latest(&history));
+    for version in [from, to] {
+        if version > latest(&history) {
+            bail!("There is no version {version}");
+        }
+    }
+
+    Ok(BlocklistChange::between(
+        &auto_ban::avatar_file(),
+        &entries_at(&history, from).into_iter().collect(),
+        &entries_at(&history, to).into_iter().collect(),
+    ))
+}
+
+/// Restores the list as it was at `version`, recorded as a new version.
+pub async fn rollback(version: u64, author: &str, reason: &str) -> Result<BlocklistVersion> {
+    let mut history = HISTORY.lock().await;
+    sync_locked(&mut history)?;
+
+    if version >= latest(&history) {
+        bail!(
+            "Version {version} is not older than the latest version {}",
+            latest(&history)
+        );
+    }
+
+    let target = entries_at(&history, version);
+    let current = entries_at(&history, latest(&history));
+    let removed = current.difference(&target).cloned().collect::<HashSet<_>>();
+    let added = target.difference(&current).cloned().collect::<Vec<_>>();
+
+    let rolled_back = apply(
+        Path::new(&auto_ban::avatar_file()),
+        || rewrite(&removed, &added),
+        || record(&mut history, &target, author, reason, Some(version)),
+    )?
+    .with_context(|| format!("The list already matches version {version}"))?;
+    drop(history);
+
+    info!(
+        "{} rolled the blocklist back to version {}: {} added, {} removed",
+        author,
+        version,
+        rolled_back.added.len(),
+        rolled_back.removed.len()
+    );
+
+    EVENT_BUS
+        .publish(AppEvent::OnBlocklistRolledBack(rolled_back.clone()))
+        .await;
+
+    Ok(rolled_back)
+}
+
+pub fn init() {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = sync_locked(&mut *HISTORY.lock().await) {
+                error!("Failed to sync blocklist history: {:#}", e);
+            }
+
+            tokio::time::sleep(SYNC_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: u64, added: &[&str], removed: &[&str]) -> BlocklistVersion {
+        BlocklistVersion {
+            version,
+            author: "test".to_string(),
+            reason: String::new(),
+            added: added.iter().map(|id| id.to_string()).collect(),
+            removed: removed.iter().map(|id| id.to_string()).collect(),
+            rollback_to: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn entries(ids: &[&str]) -> BTreeSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("vrc-manager-{}-{name}", std::process::id()))
+    }
+
+    fn append(path: &Path, id: &str) -> Result<()> {
+        util::append_line(path.to_str().unwrap(), id).context("Failed to update avatar list")
+    }
+
+    fn history() -> Vec<BlocklistVersion> {
+        vec![
+            version(1, &["file_a", "file_b"], &[]),
+            version(2, &["file_c"], &["file_a"]),
+            version(3, &["file_a"], &["file_b"]),
+        ]
+    }
+
+    #[test]
+    fn replays_adds_and_removes() {
+        let history = history();
+
+        assert_eq!(entries_at(&history, 0), entries(&[]));
+        assert_eq!(entries_at(&history, 1), entries(&["file_a", "file_b"]));
+        assert_eq!(entries_at(&history, 2), entries(&["file_b", "file_c"]));
+        assert_eq!(entries_at(&history, 3), entries(&["file_a", "file_c"]));
+    }
+
+    #[test]
+    fn stops_at_requested_version() {
+        let history = history();
+
+        assert_eq!(entries_at(&history, 99), entries_at(&history, 3));
+        assert_eq!(entries_at(&[], 5), entries(&[]));
+    }
+
+    #[test]
+    fn removes_before_adding_within_a_version() {
+        let history = vec![
+            version(1, &["file_a"], &[]),
+            version(2, &["file_a"], &["file_a"]),
+        ];
+
+        assert_eq!(entries_at(&history, 2), entries(&["file_a"]));
+    }
+
+    #[test]
+    fn keeps_change_once_version_is_saved() {
+        let path = temp_file("saved.txt");
+        fs::write(&path, "file_a\n").unwrap();
+
+        let saved = apply(&path, || append(&path, "file_b"), || Ok(1)).unwrap();
+
+        assert_eq!(saved, 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "file_a\nfile_b\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn restores_file_when_version_is_not_saved() {
+        let path = temp_file("restored.txt");
+        let contents = b"# blocked\nfile_a\n\xff\n";
+        fs::write(&path, contents).unwrap();
+
+        let result = apply(
+            &path,
+            || append(&path, "file_b"),
+            || -> Result<()> { bail!("Failed to save blocklist history") },
+        );
+
+        assert!(result.is_err());
+        assert_eq!(fs::read(&path).unwrap(), contents);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn removes_created_file_when_version_is_not_saved() {
+        let path = temp_file("created.txt");
+        let _ = fs::remove_file(&path);
+
+        let result = apply(
+            &path,
+            || append(&path, "file_a"),
+            || -> Result<()> { bail!("Failed to save blocklist history") },
+        );
+
+        assert!(result.is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,7 @@
+//! Blocklists, the formats and signatures come from the library shared with `blocklist-server`.
+
+pub use vrc_manager::blocklist::*;
+
+pub mod cli;
+pub mod history;
+pub mod remote;
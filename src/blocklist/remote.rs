@@ -0,0 +1,349 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use super::{BlocklistChange, BlocklistEntry, BlocklistFile, parse_lines, signing};
+use crate::config::{BlocklistSource, CONFIG, Trust};
+use crate::events::{AppEvent, EVENT_BUS};
+use crate::storage;
+
+const MIN_REFRESH_INTERVAL: u64 = 300;
+const DEFAULT_REFRESH_INTERVAL: u64 = 3600;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Far above any real list, but a misbehaving source can't exhaust memory or disk.
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+
+/// The last copy of a remote list, with what's needed for a conditional request.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+struct Cached {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: Option<DateTime<Utc>>,
+    /// The list's own `updated_at`, so an older signed copy can't be replayed.
+    updated_at: Option<DateTime<Utc>>,
+    /// Who verified this copy, it can't stand in for another publisher's list.
+    publisher: Option<String>,
+    entries: Vec<BlocklistEntry>,
+}
+
+impl Cached {
+    fn ids(&self) -> HashSet<String> {
+        self.entries.iter().map(|entry| entry.id.clone()).collect()
+    }
+}
+
+/// Whether a cached copy may be used for `source` as it's configured now. A copy saved before
+/// signatures were required, or verified by another publisher, waits for a verified download.
+fn is_trusted(source: &BlocklistSource, cached: &Cached) -> bool {
+    if source.publisher.is_none() && !CONFIG.blocklists.require_signatures {
+        return true;
+    }
+
+    cached.publisher.is_some() && cached.publisher == source.publisher
+}
+
+static LISTS: Lazy<Mutex<HashMap<String, Cached>>> = Lazy::new(|| {
+    let lists = CONFIG
+        .blocklists
+        .sources
+        .iter()
+        .filter_map(|source| {
+            let cached: Cached = storage::load(&cache_file(source));
+            if !is_trusted(source, &cached) {
+                if cached.fetched_at.is_some() {
+                    warn!(
+                        "Ignoring the cached copy of blocklist {}: it wasn't verified by its publisher",
+                        source.name
+                    );
+                }
+                return None;
+            }
+
+            Some((source.name.clone(), cached))
+        })
+        .collect();
+
+    Mutex::new(lists)
+});
+
+fn cache_file(source: &BlocklistSource) -> String {
+    format!("blocklists/{}.json", source.name)
+}
+
+/// A remote list entry matching a user.
+#[derive(Clone, Debug)]
+pub struct RemoteMatch {
+    pub source: String,
+    pub url: String,
+    pub trust: Trust,
+    pub reason: Option<String>,
+}
+
+fn overrides() -> HashSet<String> {
+    let path = CONFIG
+        .blocklists
+        .overrides_file
+        .clone()
+        .unwrap_or("blocklist_overrides.txt".to_string());
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => parse_lines(&contents).into_iter().collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+        Err(e) => {
+            error!("Failed to read {}: {}", path, e);
+            HashSet::new()
+        }
+    }
+}
+
+/// Finds `id` in the subscribed lists, preferring sources trusted to ban.
+/// Entries in the local overrides file never match.
+pub async fn lookup(id: &str) -> Option<RemoteMatch> {
+    if overrides().contains(id) {
+        return None;
+    }
+
+    let lists = LISTS.lock().await;
+    let mut matches = CONFIG
+        .blocklists
+        .sources
+        .iter()
+        .filter_map(|source| {
+            let entry = lists
+                .get(&source.name)?
+                .entries
+                .iter()
+                .find(|entry| entry.id == id)?;
+
+            Some(RemoteMatch {
+                source: source.name.clone(),
+                url: source.url.clone(),
+                trust: source.trust,
+                reason: entry.reason.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    matches.sort_by_key(|found| found.trust != Trust::Ban);
+    matches.into_iter().next()
+}
+
+/// Every subscribed source with its entry count and when it was last fetched.
+pub async fn summary() -> Vec<(String, usize, Option<DateTime<Utc>>)> {
+    let lists = LISTS.lock().await;
+
+    CONFIG
+        .blocklists
+        .sources
+        .iter()
+        .map(|source| {
+            let cached = lists.get(&source.name);
+            (
+                source.name.clone(),
+                cached.map_or(0, |cached| cached.entries.len()),
+                cached.and_then(|cached| cached.fetched_at),
+            )
+        })
+        .collect()
+}
+
+/// Every ID in the subscribed lists, for resolving the avatars among them.
+pub async fn entry_ids() -> HashSet<String> {
+    LISTS
+        .lock()
+        .await
+        .values()
+        .flat_map(|cached| cached.ids())
+        .collect()
+}
+
+/// Whether any subscribed list has an entry matching `predicate`.
+pub async fn any_entry(predicate: impl Fn(&str) -> bool) -> bool {
+    LISTS
+        .lock()
+        .await
+        .values()
+        .any(|cached| cached.entries.iter().any(|entry| predicate(&entry.id)))
+}
+
+/// Reads a response body, giving up once it grows past `MAX_BODY_BYTES`.
+async fn read_body(mut response: Response) -> Result<Vec<u8>> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_BODY_BYTES as u64)
+    {
+        bail!("Response is larger than {MAX_BODY_BYTES} bytes");
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_BODY_BYTES {
+            bail!("Response is larger than {MAX_BODY_BYTES} bytes");
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+/// Rejects lists whose signature doesn't check out, before any entry is taken over.
+async fn verify_signature(source: &BlocklistSource, body: &[u8]) -> Result<()> {
+    let Some(publisher) = &source.publisher else {
+        if CONFIG.blocklists.require_signatures {
+            bail!("No publisher set, but require_signatures is on");
+        }
+        return Ok(());
+    };
+
+    let public_key = CONFIG
+        .blocklists
+        .publishers
+        .get(publisher)
+        .with_context(|| format!("Unknown publisher {publisher}"))?;
+
+    let signature_url = source
+        .signature_url
+        .clone()
+        .unwrap_or_else(|| format!("{}.sig", source.url));
+    let response = CLIENT
+        .get(&signature_url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Failed to fetch the signature")?;
+    let signature =
+        String::from_utf8(read_body(response).await?).context("Signature is not valid UTF-8")?;
+
+    signing::verify(public_key, body, &signature)
+        .with_context(|| format!("Rejected the list from {publisher}"))
+}
+
+async fn refresh(source: &BlocklistSource) -> Result<()> {
+    let previous = LISTS
+        .lock()
+        .await
+        .get(&source.name)
+        .cloned()
+        .unwrap_or_default();
+
+    // A copy verified by another publisher has to be downloaded and verified again.
+    let same_publisher = previous.publisher == source.publisher;
+
+    let mut request = CLIENT.get(&source.url);
+    if same_publisher && let Some(etag) = &previous.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if same_publisher && let Some(last_modified) = &previous.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await.context("Request failed")?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+
+    let mut cached = if response.status() == StatusCode::NOT_MODIFIED {
+        previous.clone()
+    } else {
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = read_body(
+            response
+                .error_for_status()
+                .context("Server refused the request")?,
+        )
+        .await?;
+
+        verify_signature(source, &body).await?;
+        let body = String::from_utf8(body).context("List is not valid UTF-8")?;
+        let list = BlocklistFile::parse(&body)?;
+
+        // An old list carries a valid signature forever, so it must not replace a newer one.
+        if source.publisher.is_some() {
+            let Some(updated_at) = list.updated_at else {
+                bail!("Rejected a signed list without updated_at, signed lists must be JSON");
+            };
+            if same_publisher
+                && previous
+                    .updated_at
+                    .is_some_and(|cached| updated_at < cached)
+            {
+                bail!("Rejected a signed list older than the cached one");
+            }
+        }
+
+        Cached {
+            etag,
+            last_modified,
+            fetched_at: None,
+            updated_at: list.updated_at,
+            publisher: source.publisher.clone(),
+            entries: list.entries,
+        }
+    };
+    cached.fetched_at = Some(Utc::now());
+
+    let change = BlocklistChange::between(&source.name, &previous.ids(), &cached.ids());
+
+    storage::save(&cache_file(source), &cached)?;
+    LISTS.lock().await.insert(source.name.clone(), cached);
+
+    // The first download of a list isn't news, only what changes afterwards.
+    if !change.is_empty() && previous.fetched_at.is_some() {
+        info!(
+            "Blocklist {} updated: {} added, {} removed",
+            source.name,
+            change.added.len(),
+            change.removed.len()
+        );
+        EVENT_BUS
+            .publish(AppEvent::OnBlocklistUpdated(change))
+            .await;
+    }
+
+    Ok(())
+}
+
+pub fn init() {
+    let settings = &CONFIG.blocklists;
+
+    if settings.sources.is_empty() {
+        return;
+    }
+
+    let interval = match settings.refresh_interval {
+        0 => DEFAULT_REFRESH_INTERVAL,
+        seconds => seconds.max(MIN_REFRESH_INTERVAL),
+    };
+
+    tokio::spawn(async move {
+        loop {
+            for source in &CONFIG.blocklists.sources {
+                if let Err(e) = refresh(source).await {
+                    error!("Failed to refresh blocklist {}: {:#}", source.name, e);
+                }
+            }
+
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    });
+}
@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Carries the hex signature of a submission's body.
+pub const SIGNATURE_HEADER: &str = "X-Blocklist-Signature";
+
+fn decode_key(key: &str, what: &str) -> Result<[u8; 32]> {
+    hex::decode(key.trim())
+        .with_context(|| format!("{what} is not valid hex"))?
+        .try_into()
+        .ok()
+        .with_context(|| format!("{what} must be 32 bytes"))
+}
+
+fn parse_public_key(key: &str) -> Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&decode_key(key, "Public key")?)
+        .context("Public key is not a valid Ed25519 key")
+}
+
+/// Checks a detached, hex encoded Ed25519 signature over the exact bytes of a list.
+pub fn verify(public_key: &str, body: &[u8], signature: &str) -> Result<()> {
+    let public_key = parse_public_key(public_key)?;
+    let signature = hex::decode(signature.trim())
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .context("Signature is not 64 hex encoded bytes")?;
+
+    public_key
+        .verify_strict(body, &signature)
+        .context("Signature does not match the publisher key")
+}
+
+pub fn generate_key() -> SigningKey {
+    SigningKey::from_bytes(&rand::random())
+}
+
+/// Reads a signing key saved as hex, like `generate_key` output written by `encode_key`.
+pub fn decode_signing_key(contents: &str) -> Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&decode_key(
+        contents,
+        "Signing key",
+    )?))
+}
+
+pub fn encode_key(key: &SigningKey) -> String {
+    hex::encode(key.to_bytes())
+}
+
+/// Saves a new signing key readable by its owner only, never replacing an existing file.
+pub fn write_signing_key(path: &Path, key: &SigningKey) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(encode_key(key).as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn public_key(key: &SigningKey) -> String {
+    hex::encode(key.verifying_key().to_bytes())
+}
+
+/// A detached, hex encoded signature over `body`.
+pub fn sign(key: &SigningKey, body: &[u8]) -> String {
+    hex::encode(key.sign(body).to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"entries":["file_1"],"updated_at":"2026-01-01T00:00:00Z"}"#;
+
+    #[test]
+    fn accepts_own_signature() {
+        let key = generate_key();
+        let signature = sign(&key, BODY);
+
+        assert!(verify(&public_key(&key), BODY, &signature).is_ok());
+        assert!(verify(&format!(" {}\n", public_key(&key)), BODY, &signature).is_ok());
+    }
+
+    #[test]
+    fn rejects_tampered_body() {
+        let key = generate_key();
+        let signature = sign(&key, BODY);
+
+        let mut tampered = BODY.to_vec();
+        tampered[14] ^= 1;
+        assert!(verify(&public_key(&key), &tampered, &signature).is_err());
+        assert!(verify(&public_key(&key), &BODY[..BODY.len() - 1], &signature).is_err());
+    }
+
+    #[test]
+    fn rejects_other_publisher() {
+        let signature = sign(&generate_key(), BODY);
+
+        assert!(verify(&public_key(&generate_key()), BODY, &signature).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let key = generate_key();
+        let signature = sign(&key, BODY);
+
+        assert!(verify(&public_key(&key), BODY, "not hex").is_err());
+        assert!(verify(&public_key(&key), BODY, &signature[..64]).is_err());
+        assert!(verify("abcd", BODY, &signature).is_err());
+    }
+
+    #[test]
+    fn round_trips_signing_key() {
+        let key = generate_key();
+        let decoded = decode_signing_key(&format!("{}\n", encode_key(&key))).unwrap();
+
+        assert_eq!(public_key(&decoded), public_key(&key));
+        assert!(decode_signing_key("abcd").is_err());
+    }
+}
@@ -1,21 +1,129 @@
+use anyhow::{Result, bail};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use tracing::error;
 
+use crate::discord_webhook::routing::WebhookTarget;
+use crate::discord_webhook::template::EmbedTemplate;
+use crate::notifiers::Notifiers;
+use crate::vrchat::criteria::Criteria;
+
 #[derive(Deserialize, Default, Debug)]
 #[serde(default)]
+pub struct InviteQuota {
+    pub per_hour: u32,
+    pub per_day: u32,
+    pub per_session: u32,
+    pub prioritize_longest_stay: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
 pub struct AutoInvite {
     pub enabled: bool,
     pub delay_min: u64,
     pub delay_max: u64,
+    pub recheck_before_invite: bool,
+    pub cancel_on_instance_change: bool,
+    pub criteria: Criteria,
+    pub quota: InviteQuota,
+}
+
+impl Default for AutoInvite {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_min: 0,
+            delay_max: 0,
+            recheck_before_invite: false,
+            cancel_on_instance_change: true,
+            criteria: Criteria::default(),
+            quota: InviteQuota::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeoutAction {
+    #[default]
+    Discard,
+    Approve,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct BanApproval {
+    pub enabled: bool,
+    pub all: bool,
+    pub review_file: Option<String>,
+    pub timeout_minutes: u64,
+    pub on_timeout: TimeoutAction,
 }
 
 #[derive(Deserialize, Default, Debug)]
 #[serde(default)]
 pub struct AutoBan {
     pub enabled: bool,
+    pub evidence: bool,
+    pub approval: BanApproval,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct JoinRequests {
+    pub enabled: bool,
+    pub poll_interval: u64,
+    pub block_rejected: bool,
+    pub reject: Criteria,
+    pub accept: Criteria,
+}
+
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleAction {
+    #[default]
+    Grant,
+    Revoke,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct RoleRule {
+    pub role_id: String,
+    pub action: RoleAction,
+    pub min_encounters: Option<u32>,
+    pub min_hours_present: Option<f64>,
+    pub on_blocked_avatar: bool,
+    pub criteria: Criteria,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct AutoRoles {
+    pub enabled: bool,
+    pub rules: Vec<RoleRule>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct RosterMessage {
+    pub enabled: bool,
+    pub target: Option<String>,
+    pub update_interval: u64,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Interactions {
+    pub enabled: bool,
+    pub listen: Option<String>,
+    pub public_key: String,
+    pub allowed_roles: Vec<String>,
+    pub allowed_users: Vec<String>,
 }
 
 #[derive(Deserialize, Default, Debug)]
@@ -25,11 +133,103 @@ pub struct DiscordWebhook {
     pub username: String,
     pub avatar_url: String,
     pub url: String,
+    pub max_attempts: Option<u32>,
     pub log_on_player_joined: bool,
     pub log_on_player_left: bool,
     pub log_on_avatar_changed: bool,
     pub log_on_auto_invite: bool,
     pub log_on_auto_ban: bool,
+    pub log_on_invite_quota: bool,
+    pub log_on_join_request: bool,
+    pub log_on_role_change: bool,
+    pub log_on_watchlist_hit: bool,
+    pub log_on_session_report: bool,
+    pub log_on_ban_approval: bool,
+    pub log_on_group_activity: bool,
+    pub log_on_blocklist: bool,
+    pub roster: RosterMessage,
+    pub interactions: Interactions,
+    pub templates: HashMap<String, EmbedTemplate>,
+    pub targets: Vec<WebhookTarget>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct EventWebhook {
+    pub enabled: bool,
+    pub url: String,
+    pub secret: Option<String>,
+    pub events: Vec<String>,
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Mqtt {
+    pub enabled: bool,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: Option<String>,
+    pub topics: HashMap<String, String>,
+    pub commands: bool,
+}
+
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Trust {
+    Ban,
+    #[default]
+    Alert,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct BlocklistSource {
+    pub name: String,
+    pub url: String,
+    pub trust: Trust,
+    /// Name of a key in `publishers`, the list must then carry a valid signature
+    /// and be JSON with an `updated_at`.
+    pub publisher: Option<String>,
+    pub signature_url: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Blocklists {
+    pub refresh_interval: u64,
+    pub overrides_file: Option<String>,
+    pub require_signatures: bool,
+    pub publishers: HashMap<String, String>,
+    pub signing_key: Option<String>,
+    /// A `blocklist-server` to send votes to, and the moderator name it knows this key by.
+    pub server_url: Option<String>,
+    pub moderator: Option<String>,
+    pub sources: Vec<BlocklistSource>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Pardons {
+    pub enabled: bool,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct GroupAudit {
+    pub enabled: bool,
+    pub poll_interval: u64,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Api {
+    pub enabled: bool,
+    pub listen: Option<String>,
+    pub token: Option<String>,
 }
 
 #[derive(Deserialize, Default, Debug)]
@@ -37,18 +237,87 @@ pub struct DiscordWebhook {
 pub struct Config {
     pub auto_invite: AutoInvite,
     pub auto_ban: AutoBan,
+    pub join_requests: JoinRequests,
+    pub pardons: Pardons,
+    pub blocklists: Blocklists,
+    pub group_audit: GroupAudit,
+    pub auto_roles: AutoRoles,
     pub discord_webhook: DiscordWebhook,
+    pub event_webhook: EventWebhook,
+    pub notifiers: Notifiers,
+    pub mqtt: Mqtt,
+    pub api: Api,
     pub log_avatar_id: bool,
+    pub session_reports: bool,
+    pub weekly_digest: bool,
     pub group_id: Option<String>,
     pub avatars_file: Option<String>,
+    pub watchlist_file: Option<String>,
     pub custom_log_dir: Option<String>,
+    pub data_dir: Option<String>,
+}
+
+/// Checks that names used in file names are safe path components and unique.
+fn validate_names<'a>(kind: &str, names: impl Iterator<Item = &'a str>) -> Result<()> {
+    let mut seen = HashSet::new();
+
+    for name in names {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            bail!("{kind} name \"{name}\" may only contain letters, digits, '_' and '-'");
+        }
+        if !seen.insert(name) {
+            bail!("{kind} name \"{name}\" is used more than once");
+        }
+    }
+
+    Ok(())
+}
+
+impl Config {
+    fn validate(&self) -> Result<()> {
+        validate_names(
+            "Discord webhook target",
+            self.discord_webhook
+                .targets
+                .iter()
+                .map(|target| target.name.as_str()),
+        )?;
+        validate_names(
+            "Blocklist source",
+            self.blocklists
+                .sources
+                .iter()
+                .map(|source| source.name.as_str()),
+        )?;
+
+        let invite = &self.auto_invite;
+        if invite.delay_min > invite.delay_max {
+            bail!(
+                "auto_invite.delay_min ({}) is greater than delay_max ({})",
+                invite.delay_min,
+                invite.delay_max
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn parse(contents: &str) -> Result<Config> {
+    let config: Config = toml::from_str(contents)?;
+    config.validate()?;
+    Ok(config)
 }
 
 pub static CONFIG: Lazy<Config> = Lazy::new(|| {
     let config_path = Path::new("config.toml");
 
     match fs::read_to_string(config_path) {
-        Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+        Ok(contents) => parse(&contents).unwrap_or_else(|e| {
             error!("Failed to parse config.toml: {e}");
             Config::default()
         }),
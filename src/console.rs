@@ -0,0 +1,396 @@
+use chrono::{Local, Utc};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::error;
+use vrchatapi::apis::configuration::Configuration;
+
+use crate::blocklist::{history, remote};
+use crate::storage;
+use crate::vrchat::{audit, auto_invite, automation, ban_approval, evidence};
+
+async fn handle_command(config: &Configuration, line: &str) {
+    let mut args = line.split_whitespace();
+
+    match args.next() {
+        Some("invites") => print_pending_invites().await,
+        Some("pause") => set_paused(args.next(), true).await,
+        Some("resume") => set_paused(args.next(), false).await,
+        Some("bans") => print_pending_bans().await,
+        Some("approve") => resolve_ban(config, args.next(), true).await,
+        Some("discard") => resolve_ban(config, args.next(), false).await,
+        Some("evidence") => print_evidence(args.next()).await,
+        Some("blocklists") => print_blocklists().await,
+        Some("block") => edit_blocklist(args.collect(), true).await,
+        Some("unblock") => edit_blocklist(args.collect(), false).await,
+        Some("history") => print_history(args.next()).await,
+        Some("diff") => print_diff(args.next(), args.next()).await,
+        Some("rollback") => rollback(args.collect()).await,
+        Some("audit") => print_audit_log(args.next()).await,
+        Some("undo") => undo(config, args.collect()).await,
+        Some("help") => print_help(),
+        Some(command) => println!("Unknown command: {command} (type \"help\" for a list)"),
+        None => (),
+    }
+}
+
+fn print_help() {
+    println!("Available commands:");
+    println!("  invites  - list pending automatic invites");
+    println!(
+        "  pause    - pause automation: pause [all|auto_ban|auto_invite|auto_roles|join_requests]"
+    );
+    println!("  resume   - resume automation: resume [all|auto_ban|...]");
+    println!("  bans     - list automatic bans awaiting approval");
+    println!("  approve  - approve a pending ban: approve <id>");
+    println!("  discard  - discard a pending ban: discard <id>");
+    println!("  evidence - show the evidence saved for a ban: evidence <id>");
+    println!("  blocklists - list subscribed remote blocklists");
+    println!("  block    - add an avatar to the blocklist: block <id> [reason]");
+    println!("  unblock  - remove an avatar from the blocklist: unblock <id> [reason]");
+    println!("  history  - list blocklist versions: history [count]");
+    println!("  diff     - compare blocklist versions: diff <from> [to]");
+    println!("  rollback - restore a blocklist version: rollback <version> [reason]");
+    println!("  audit    - list recent group actions: audit [count]");
+    println!("  undo     - undo an action: undo <id> | undo rule <rule> <since> [until]");
+    println!("  help     - show this message");
+}
+
+async fn set_paused(name: Option<&str>, paused: bool) {
+    let name = name.unwrap_or(automation::ALL);
+    let result = if paused {
+        automation::pause(name).await
+    } else {
+        automation::resume(name).await
+    };
+
+    match result {
+        Ok(()) => match automation::paused().await {
+            paused if paused.is_empty() => println!("All automation is running"),
+            paused => println!("Paused: {}", paused.join(", ")),
+        },
+        Err(e) => println!("{e}"),
+    }
+}
+
+async fn print_pending_bans() {
+    let pending = ban_approval::pending().await;
+
+    if pending.is_empty() {
+        println!("No bans awaiting approval");
+        return;
+    }
+
+    let now = Utc::now();
+    for ban in pending {
+        println!(
+            "#{} {} ({}) expires in {}m{}: {}",
+            ban.id,
+            ban.snapshot.user.display_name,
+            ban.snapshot.id(),
+            (ban.expires_at - now).num_minutes().max(0),
+            ban.evidence_id
+                .map(|id| format!(" [evidence #{id}]"))
+                .unwrap_or_default(),
+            ban.reason
+        );
+    }
+}
+
+async fn resolve_ban(config: &Configuration, id: Option<&str>, approve: bool) {
+    let Some(id) = id.and_then(|id| id.trim_start_matches('#').parse().ok()) else {
+        println!(
+            "Usage: {} <id>",
+            if approve { "approve" } else { "discard" }
+        );
+        return;
+    };
+
+    let result = if approve {
+        ban_approval::approve(config, id, "console").await
+    } else {
+        ban_approval::discard(id, "console").await
+    };
+
+    match result {
+        Ok(ban) if approve => println!("Banned {}", ban.snapshot.user.display_name),
+        Ok(ban) => println!("Discarded ban of {}", ban.snapshot.user.display_name),
+        Err(e) => println!("{e:#}"),
+    }
+}
+
+async fn print_evidence(id: Option<&str>) {
+    let Some(id) = id.and_then(|id| id.trim_start_matches('#').parse().ok()) else {
+        println!("Usage: evidence <id>");
+        return;
+    };
+
+    let Some(bundle) = evidence::get(id).await else {
+        println!("No evidence #{id}");
+        return;
+    };
+
+    println!(
+        "#{} {} ({}) {:?} at {}",
+        bundle.id,
+        bundle.display_name,
+        bundle.user_id,
+        bundle.outcome,
+        bundle.detected_at.with_timezone(&Local)
+    );
+    println!(
+        "  matched {} `{}` from {}",
+        bundle.matched.rule, bundle.matched.entry, bundle.matched.source
+    );
+    println!("  {}", storage::path(&format!("evidence/{id}")).display());
+}
+
+async fn print_blocklists() {
+    let sources = remote::summary().await;
+
+    if sources.is_empty() {
+        println!("No remote blocklists configured");
+        return;
+    }
+
+    for (name, entries, fetched_at) in sources {
+        let fetched = fetched_at.map_or("never fetched".to_string(), |time| {
+            format!(
+                "fetched {}",
+                time.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            )
+        });
+        println!("{name}: {entries} entries, {fetched}");
+    }
+}
+
+async fn edit_blocklist(args: Vec<&str>, block: bool) {
+    let Some((id, reason)) = args.split_first() else {
+        println!(
+            "Usage: {} <id> [reason]",
+            if block { "block" } else { "unblock" }
+        );
+        return;
+    };
+
+    let reason = match reason.join(" ") {
+        reason if reason.is_empty() => "No reason given".to_string(),
+        reason => reason,
+    };
+
+    let result = if block {
+        history::add(id, "console", &reason).await
+    } else {
+        history::remove(id, "console", &reason).await
+    };
+
+    match result {
+        Ok(true) if block => println!("Added {id} to the blocklist"),
+        Ok(true) => println!("Removed {id} from the blocklist"),
+        Ok(false) if block => println!("{id} is already blocked"),
+        Ok(false) => println!("{id} is not on the blocklist"),
+        Err(e) => println!("{e:#}"),
+    }
+}
+
+async fn print_history(count: Option<&str>) {
+    let count = count.and_then(|count| count.parse().ok()).unwrap_or(20);
+    let versions = history::versions().await;
+
+    if versions.is_empty() {
+        println!("The blocklist has no history yet");
+        return;
+    }
+
+    for version in versions.iter().rev().take(count).rev() {
+        let rollback = version
+            .rollback_to
+            .map(|to| format!(" [rollback to {to}]"))
+            .unwrap_or_default();
+
+        println!(
+            "v{} {} by {}{}: +{} -{} ({})",
+            version.version,
+            version
+                .timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            version.author,
+            rollback,
+            version.added.len(),
+            version.removed.len(),
+            version.reason
+        );
+    }
+}
+
+fn parse_version(version: &str) -> Option<u64> {
+    version.trim_start_matches('v').parse().ok()
+}
+
+async fn print_diff(from: Option<&str>, to: Option<&str>) {
+    let Some(from) = from.and_then(parse_version) else {
+        println!("Usage: diff <from> [to]");
+        return;
+    };
+    let to = to.and_then(parse_version);
+
+    match history::diff(from, to).await {
+        Ok(change) if change.is_empty() => println!("No differences"),
+        Ok(change) => {
+            for id in change.added {
+                println!("+ {id}");
+            }
+            for id in change.removed {
+                println!("- {id}");
+            }
+        }
+        Err(e) => println!("{e:#}"),
+    }
+}
+
+async fn rollback(args: Vec<&str>) {
+    let Some((version, reason)) = args
+        .split_first()
+        .and_then(|(version, reason)| Some((parse_version(version)?, reason)))
+    else {
+        println!("Usage: rollback <version> [reason]");
+        return;
+    };
+
+    let reason = match reason.join(" ") {
+        reason if reason.is_empty() => format!("Rolled back to version {version}"),
+        reason => reason,
+    };
+
+    match history::rollback(version, "console", &reason).await {
+        Ok(rolled_back) => println!(
+            "Restored version {version} as v{}: +{} -{}",
+            rolled_back.version,
+            rolled_back.added.len(),
+            rolled_back.removed.len()
+        ),
+        Err(e) => println!("{e:#}"),
+    }
+}
+
+async fn print_audit_log(count: Option<&str>) {
+    let count = count.and_then(|count| count.parse().ok()).unwrap_or(20);
+    let entries = audit::entries().await;
+
+    if entries.is_empty() {
+        println!("The audit log is empty");
+        return;
+    }
+
+    for entry in entries.iter().rev().take(count).rev() {
+        let outcome = match &entry.error {
+            Some(error) => format!("failed: {error}"),
+            None => "ok".to_string(),
+        };
+
+        println!(
+            "#{} {} {} by {}{}{} ({}): {}",
+            entry.id,
+            entry
+                .timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            entry.action,
+            entry.actor,
+            entry
+                .rule
+                .as_ref()
+                .map(|rule| format!(" [{rule}]"))
+                .unwrap_or_default(),
+            entry
+                .evidence_id
+                .map(|id| format!(" [evidence #{id}]"))
+                .unwrap_or_default(),
+            outcome,
+            entry.reason
+        );
+    }
+}
+
+async fn undo(config: &Configuration, args: Vec<&str>) {
+    match args.as_slice() {
+        ["rule", rule, since, until @ ..] if until.len() <= 1 => {
+            let window = audit::parse_time(since).and_then(|since| {
+                let until = until
+                    .first()
+                    .map_or(Ok(Utc::now()), |until| audit::parse_time(until))?;
+                Ok((since, until))
+            });
+
+            let (since, until) = match window {
+                Ok(window) => window,
+                Err(e) => {
+                    println!("{e:#}");
+                    return;
+                }
+            };
+
+            let (undone, failed) = audit::undo_rule(config, rule, since, until, "console").await;
+            println!("Undid {} action(s) of {}", undone.len(), rule);
+            for (id, e) in failed {
+                println!("  #{id} failed: {e:#}");
+            }
+        }
+        [id] => {
+            let Ok(id) = id.trim_start_matches('#').parse() else {
+                println!("Usage: undo <id> | undo rule <rule> <since> [until]");
+                return;
+            };
+
+            match audit::undo(config, id, "console").await {
+                Ok(entry) => println!("Undid #{id} as #{}: {}", entry.id, entry.action),
+                Err(e) => println!("{e:#}"),
+            }
+        }
+        _ => println!("Usage: undo <id> | undo rule <rule> <since> [until]"),
+    }
+}
+
+async fn print_pending_invites() {
+    let pending = auto_invite::pending_invites().await;
+
+    if pending.is_empty() {
+        println!("No pending invites");
+        return;
+    }
+
+    let now = Local::now();
+    for invite in pending {
+        let status = if invite.due > now {
+            format!("in {}s", (invite.due - now).num_seconds())
+        } else {
+            "held back by quota".to_string()
+        };
+
+        println!(
+            "{} ({}) {} [{}]",
+            invite.display_name,
+            invite.user_id,
+            status,
+            invite.location.as_deref().unwrap_or("unknown instance")
+        );
+    }
+}
+
+pub fn init(config: &Configuration) {
+    let config = config.clone();
+
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+        loop {
+            match lines.next_line().await {
+                Ok(Some(line)) => handle_command(&config, line.trim()).await,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Failed to read console input: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::Utc;
+use ed25519_dalek::{Signature, VerifyingKey};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::{error, info};
+use vrchatapi::apis::configuration::Configuration as ApiConfig;
+
+use crate::blocklist::history;
+use crate::config::CONFIG;
+use crate::vrchat::{audit, avatar_info, ban_approval, watchlist};
+
+const PING: u8 = 1;
+const MESSAGE_COMPONENT: u8 = 3;
+const PONG: u8 = 1;
+const DEFERRED_CHANNEL_MESSAGE: u8 = 5;
+const EPHEMERAL: u32 = 1 << 6;
+/// How old a signed request may be, so a captured one can't be replayed later.
+const MAX_REQUEST_AGE: i64 = 5 * 60;
+
+const STYLE_SECONDARY: u8 = 2;
+const STYLE_SUCCESS: u8 = 3;
+const STYLE_DANGER: u8 = 4;
+
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// A moderator action offered as a button below an embed.
+#[derive(Clone, Debug)]
+pub enum Action {
+    Ban(String),
+    Unban(String),
+    BlockAvatar(String),
+    /// Blocks the author of the avatar with this image file.
+    BlockAuthor(String),
+    Watchlist(String),
+    ApproveBan(u64),
+    DiscardBan(u64),
+}
+
+impl Action {
+    fn custom_id(&self) -> String {
+        match self {
+            Action::Ban(user_id) => format!("ban:{user_id}"),
+            Action::Unban(user_id) => format!("unban:{user_id}"),
+            Action::BlockAvatar(avatar_id) => format!("block_avatar:{avatar_id}"),
+            Action::BlockAuthor(avatar_id) => format!("block_author:{avatar_id}"),
+            Action::Watchlist(user_id) => format!("watchlist:{user_id}"),
+            Action::ApproveBan(id) => format!("approve_ban:{id}"),
+            Action::DiscardBan(id) => format!("discard_ban:{id}"),
+        }
+    }
+
+    fn parse(custom_id: &str) -> Option<Self> {
+        let (kind, id) = custom_id.split_once(':')?;
+        let id = id.to_string();
+
+        match kind {
+            "ban" => Some(Action::Ban(id)),
+            "unban" => Some(Action::Unban(id)),
+            "block_avatar" => Some(Action::BlockAvatar(id)),
+            "block_author" => Some(Action::BlockAuthor(id)),
+            "watchlist" => Some(Action::Watchlist(id)),
+            "approve_ban" => id.parse().ok().map(Action::ApproveBan),
+            "discard_ban" => id.parse().ok().map(Action::DiscardBan),
+            _ => None,
+        }
+    }
+
+    fn button(&self) -> Value {
+        let (label, style) = match self {
+            Action::Ban(_) => ("Ban user", STYLE_DANGER),
+            Action::Unban(_) => ("Unban", STYLE_SUCCESS),
+            Action::BlockAvatar(_) => ("Block this avatar", STYLE_DANGER),
+            Action::BlockAuthor(_) => ("Block avatar author", STYLE_DANGER),
+            Action::Watchlist(_) => ("Add to watchlist", STYLE_SECONDARY),
+            Action::ApproveBan(_) => ("Approve ban", STYLE_DANGER),
+            Action::DiscardBan(_) => ("Discard", STYLE_SECONDARY),
+        };
+
+        json!({
+            "type": 2,
+            "style": style,
+            "label": label,
+            "custom_id": self.custom_id(),
+        })
+    }
+}
+
+/// Buttons are only served once someone is allowed to press them.
+fn has_allowlist() -> bool {
+    let settings = &CONFIG.discord_webhook.interactions;
+    !settings.allowed_users.is_empty() || !settings.allowed_roles.is_empty()
+}
+
+/// Builds the button row for a message, or nothing when interactions are disabled.
+pub fn components(actions: &[Action]) -> Option<Value> {
+    if actions.is_empty() || !CONFIG.discord_webhook.interactions.enabled || !has_allowlist() {
+        return None;
+    }
+
+    let buttons = actions.iter().map(Action::button).collect::<Vec<_>>();
+    Some(json!([{ "type": 1, "components": buttons }]))
+}
+
+#[derive(Deserialize)]
+struct DiscordUser {
+    id: String,
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct Member {
+    user: DiscordUser,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct InteractionData {
+    custom_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Interaction {
+    #[serde(rename = "type")]
+    kind: u8,
+    application_id: String,
+    token: String,
+    data: Option<InteractionData>,
+    member: Option<Member>,
+    user: Option<DiscordUser>,
+}
+
+struct Server {
+    auth_config: ApiConfig,
+    public_key: VerifyingKey,
+}
+
+fn verify(public_key: &VerifyingKey, headers: &HeaderMap, body: &[u8]) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let (Some(signature), Some(timestamp)) = (
+        header("X-Signature-Ed25519"),
+        header("X-Signature-Timestamp"),
+    ) else {
+        return false;
+    };
+
+    let fresh = timestamp
+        .parse::<i64>()
+        .is_ok_and(|timestamp| (Utc::now().timestamp() - timestamp).abs() <= MAX_REQUEST_AGE);
+    if !fresh {
+        return false;
+    }
+
+    let Some(signature) = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        return false;
+    };
+
+    let message = [timestamp.as_bytes(), body].concat();
+    public_key.verify_strict(&message, &signature).is_ok()
+}
+
+fn is_allowed(member: Option<&Member>, user: &DiscordUser) -> bool {
+    let settings = &CONFIG.discord_webhook.interactions;
+
+    let has_role = member.is_some_and(|member| {
+        member
+            .roles
+            .iter()
+            .any(|role| settings.allowed_roles.contains(role))
+    });
+
+    has_role || settings.allowed_users.contains(&user.id)
+}
+
+async fn execute(auth_config: &ApiConfig, action: &Action, actor: &str) -> Result<String> {
+    let message = match action {
+        Action::Ban(user_id) => {
+            let action = audit::Action::Ban {
+                user_id: user_id.clone(),
+            };
+            audit::perform(auth_config, action, actor, None, "Banned from Discord").await?;
+            format!("Banned `{user_id}` from the group")
+        }
+        Action::Unban(user_id) => {
+            let action = audit::Action::Unban {
+                user_id: user_id.clone(),
+            };
+            audit::perform(auth_config, action, actor, None, "Unbanned from Discord").await?;
+            format!("Unbanned `{user_id}`")
+        }
+        Action::BlockAvatar(avatar_id) => {
+            if history::add(avatar_id, actor, "Blocked from Discord").await? {
+                format!("Added `{avatar_id}` to the avatar blocklist")
+            } else {
+                format!("`{avatar_id}` is already blocked")
+            }
+        }
+        Action::BlockAuthor(avatar_id) => {
+            let author_id = avatar_info::resolve(auth_config, avatar_id)
+                .await
+                .author_id
+                .with_context(|| format!("Failed to find the author of `{avatar_id}`"))?;
+            let reason = format!("Author of {avatar_id}, blocked from Discord");
+
+            if history::add(&author_id, actor, &reason).await? {
+                format!("Added author `{author_id}` to the avatar blocklist")
+            } else {
+                format!("Author `{author_id}` is already blocked")
+            }
+        }
+        Action::Watchlist(user_id) => {
+            if watchlist::add(user_id).context("Failed to update watchlist")? {
+                format!("Added `{user_id}` to the watchlist")
+            } else {
+                format!("`{user_id}` is already on the watchlist")
+            }
+        }
+        Action::ApproveBan(id) => {
+            let ban = ban_approval::approve(auth_config, *id, actor).await?;
+            format!("Approved ban #{id} of {}", ban.snapshot.user.display_name)
+        }
+        Action::DiscardBan(id) => {
+            let ban = ban_approval::discard(*id, actor).await?;
+            format!("Discarded ban #{id} of {}", ban.snapshot.user.display_name)
+        }
+    };
+
+    Ok(message)
+}
+
+/// Replaces the deferred "thinking" reply with the outcome of the action.
+async fn follow_up(interaction: &Interaction, content: String) {
+    let url = format!(
+        "https://discord.com/api/v10/webhooks/{}/{}/messages/@original",
+        interaction.application_id, interaction.token
+    );
+
+    let result = CLIENT
+        .patch(url)
+        .json(&json!({ "content": content }))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+
+    // The URL holds the interaction token, so it's left out of the log.
+    if let Err(e) = result {
+        error!("Failed to answer Discord interaction: {}", e.without_url());
+    }
+}
+
+async fn handle(State(server): State<Arc<Server>>, headers: HeaderMap, body: Bytes) -> Response {
+    if !verify(&server.public_key, &headers, &body) {
+        return (StatusCode::UNAUTHORIZED, "invalid request signature").into_response();
+    }
+
+    let interaction = match serde_json::from_slice::<Interaction>(&body) {
+        Ok(interaction) => interaction,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    if interaction.kind == PING {
+        return Json(json!({ "type": PONG })).into_response();
+    }
+
+    let action = interaction
+        .data
+        .as_ref()
+        .and_then(|data| data.custom_id.as_deref())
+        .and_then(Action::parse);
+
+    let (Some(action), MESSAGE_COMPONENT) = (action, interaction.kind) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let Some(user) = interaction
+        .member
+        .as_ref()
+        .map(|member| &member.user)
+        .or(interaction.user.as_ref())
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    if !is_allowed(interaction.member.as_ref(), user) {
+        info!("{} is not allowed to run {:?}", user.username, action);
+        let reply = json!({
+            "type": 4,
+            "data": { "content": "You are not allowed to do that", "flags": EPHEMERAL },
+        });
+        return Json(reply).into_response();
+    }
+
+    info!("{} ({}) requested {:?}", user.username, user.id, action);
+    let actor = format!("{} on Discord", user.username);
+
+    // Discord wants an answer within 3 seconds, so acknowledge now and report back later.
+    tokio::spawn(async move {
+        let content = match execute(&server.auth_config, &action, &actor).await {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Failed to run {:?}: {:#}", action, e);
+                format!("Failed: {e:#}")
+            }
+        };
+        follow_up(&interaction, content).await;
+    });
+
+    Json(json!({ "type": DEFERRED_CHANNEL_MESSAGE, "data": { "flags": EPHEMERAL } }))
+        .into_response()
+}
+
+fn public_key() -> Result<VerifyingKey> {
+    let bytes = hex::decode(&CONFIG.discord_webhook.interactions.public_key)
+        .context("public_key is not valid hex")?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .ok()
+        .context("public_key must be 32 bytes")?;
+
+    VerifyingKey::from_bytes(&bytes).context("public_key is not a valid Ed25519 key")
+}
+
+pub fn init(auth_config: &ApiConfig) {
+    let settings = &CONFIG.discord_webhook.interactions;
+
+    // Anyone who can see the channel could press the buttons otherwise.
+    if !has_allowlist() {
+        error!("Discord interactions disabled: set allowed_roles or allowed_users");
+        return;
+    }
+
+    let public_key = match public_key() {
+        Ok(key) => key,
+        Err(e) => {
+            error!("Discord interactions disabled: {:#}", e);
+            return;
+        }
+    };
+
+    let server = Arc::new(Server {
+        auth_config: auth_config.clone(),
+        public_key,
+    });
+    let router = Router::new()
+        .route("/interactions", post(handle))
+        .with_state(server);
+    let address = settings
+        .listen
+        .clone()
+        .unwrap_or("127.0.0.1:8787".to_string());
+
+    tokio::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!(
+                    "Failed to listen for Discord interactions on {}: {}",
+                    address, e
+                );
+                return;
+            }
+        };
+
+        info!("Listening for Discord interactions on {}", address);
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("Discord interaction endpoint failed: {}", e);
+        }
+    });
+}
@@ -1,237 +1,132 @@
-use crate::vrchat::util::extract_avatar_file_id;
-use crate::{config::CONFIG, events::AppEvent, listen};
-use reqwest::Client;
-use serde::Serialize;
-use tracing::error;
+use crate::config::CONFIG;
+use crate::notifiers::message::Message;
+use routing::WebhookTarget;
+use serde::{Deserialize, Serialize};
 use vrchatapi::apis::configuration::Configuration as ApiConfig;
-use vrchatapi::apis::users_api;
-use vrchatapi::models::User;
+
+pub mod interactions;
+mod outbox;
+mod roster;
+pub mod routing;
+pub mod template;
 
 pub fn init(auth_config: &ApiConfig) {
-    if CONFIG.discord_webhook.log_on_auto_ban {
-        let auth_config = auth_config.clone();
-        listen!(
-            AppEvent::OnAutoBanned(user_id, avatar_id) => {
-                handle_auto_ban(&auth_config, user_id, avatar_id).await;
-            }
-        );
-    }
+    outbox::start();
 
-    if CONFIG.discord_webhook.log_on_auto_invite {
-        let auth_config = auth_config.clone();
-        listen!(
-            AppEvent::OnAutoInvited(user_id) => {
-                handle_auto_invite(&auth_config, user_id).await;
-            }
-        );
+    if CONFIG.discord_webhook.roster.enabled {
+        roster::init();
     }
 
-    if CONFIG.discord_webhook.log_on_player_joined {
-        let auth_config = auth_config.clone();
-        listen!(
-            AppEvent::OnPlayerJoined(user_id, user) => {
-                handle_player_joined(&auth_config, user_id, user).await;
-            }
-        );
+    if CONFIG.discord_webhook.interactions.enabled {
+        interactions::init(auth_config);
     }
+}
 
-    if CONFIG.discord_webhook.log_on_avatar_changed {
-        let auth_config = auth_config.clone();
-        listen!(
-            AppEvent::OnAvatarChanged(user_id, user) => {
-                handle_avatar_changed(&auth_config, user_id, user).await;
-            }
-        )
-    }
+/// Whether the `log_on_*` settings let an event through to Discord at all.
+pub fn logs(event: &str) -> bool {
+    let settings = &CONFIG.discord_webhook;
 
-    if CONFIG.discord_webhook.log_on_player_left {
-        let auth_config = auth_config.clone();
-        listen!(
-            AppEvent::OnPlayerLeft(user_id, user) => {
-                handle_player_left(&auth_config, user_id, user).await;
-            }
-        );
+    match event {
+        "auto_ban" => settings.log_on_auto_ban,
+        "auto_invite" => settings.log_on_auto_invite,
+        "invite_quota" => settings.log_on_invite_quota,
+        "join_request_accepted" | "join_request_rejected" | "join_request_escalated" => {
+            settings.log_on_join_request
+        }
+        "role_granted" | "role_revoked" => settings.log_on_role_change,
+        "watchlist_hit" => settings.log_on_watchlist_hit,
+        "session_report" | "weekly_digest" => settings.log_on_session_report,
+        "ban_pending" | "ban_discarded" | "ban_failed" => settings.log_on_ban_approval,
+        "blocklist_alert" | "blocklist_updated" | "blocklist_rolled_back" => {
+            settings.log_on_blocklist
+        }
+        "player_joined" => settings.log_on_player_joined,
+        "player_left" => settings.log_on_player_left,
+        "avatar_changed" => settings.log_on_avatar_changed,
+        event if event.starts_with("group_") => settings.log_on_group_activity,
+        _ => false,
     }
 }
 
-#[derive(Serialize)]
+/// Queues a message on a target's webhook, which delivers and retries it on its own.
+pub async fn enqueue(target: &WebhookTarget, message: &Message) {
+    outbox::enqueue(
+        &target.name,
+        message.into(),
+        interactions::components(&message.actions),
+    )
+    .await;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct Embed {
     title: String,
     description: String,
     fields: Vec<Field>,
-    thumbnail: Thumbnail,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<Thumbnail>,
     color: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    footer: Option<Footer>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Field {
     name: String,
     value: String,
     inline: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Thumbnail {
     url: String,
 }
 
-#[derive(Serialize)]
-struct WebhookPayload {
-    username: String,
-    avatar_url: String,
-    embeds: Vec<Embed>,
-}
-
-async fn handle_auto_ban(auth_config: &ApiConfig, user_id: String, avatar_id: String) {
-    send_embed(auth_config, user_id, move |user| Embed {
-        title: "User Banned".into(),
-        description: format!(
-            "User **{}** has been banned for using avatar ID: `{}`",
-            user.display_name, avatar_id
-        ),
-        fields: vec![Field {
-            name: "User ID".into(),
-            value: user.id,
-            inline: false,
-        }],
-        thumbnail: Thumbnail {
-            url: user.current_avatar_thumbnail_image_url,
-        },
-        color: 0xFF0000,
-    })
-    .await;
+#[derive(Serialize, Deserialize, Clone)]
+struct Footer {
+    text: String,
 }
 
-async fn handle_auto_invite(auth_config: &ApiConfig, user_id: String) {
-    send_embed(auth_config, user_id, |user| Embed {
-        title: "User Invited".into(),
-        description: format!(
-            "User **{}** has been automatically invited",
-            user.display_name
-        ),
-        fields: vec![Field {
-            name: "User ID".into(),
-            value: user.id,
-            inline: false,
-        }],
-        thumbnail: Thumbnail {
-            url: user.current_avatar_thumbnail_image_url,
-        },
-        color: 0x0000FF,
-    })
-    .await;
-}
-
-async fn handle_player_joined(auth_config: &ApiConfig, user_id: String, user: User) {
-    let avatar_file_id = extract_avatar_file_id(&user)
-        .unwrap_or(None)
-        .unwrap_or("".to_string());
-
-    send_embed(auth_config, user_id, |user| Embed {
-        title: "Player Joined".into(),
-        description: format!("**{}** has joined the instance!", user.display_name),
-        fields: vec![
-            Field {
-                name: "User ID".into(),
-                value: user.id,
-                inline: false,
-            },
-            Field {
-                name: "Avatar File ID".into(),
-                value: avatar_file_id,
-                inline: false,
-            },
-        ],
-        thumbnail: Thumbnail {
-            url: user.current_avatar_thumbnail_image_url,
-        },
-        color: 0x00FF00,
-    })
-    .await;
-}
-
-async fn handle_avatar_changed(auth_config: &ApiConfig, user_id: String, user: User) {
-    let avatar_file_id = match extract_avatar_file_id(&user).unwrap_or(None) {
-        Some(avatar_file_id) => avatar_file_id,
-        _ => return,
-    };
-
-    send_embed(auth_config, user_id, |user| Embed {
-        title: "Avatar Changed".into(),
-        description: format!("**{}** has changed their avatar!", user.display_name),
-        fields: vec![
-            Field {
-                name: "User ID".into(),
-                value: user.id,
-                inline: false,
-            },
-            Field {
-                name: "Avatar File ID".into(),
-                value: avatar_file_id,
-                inline: false,
-            },
-        ],
-        thumbnail: Thumbnail {
-            url: user.current_avatar_thumbnail_image_url,
-        },
-        color: 0xFFFF00,
-    })
-    .await;
+impl Embed {
+    fn char_count(&self) -> usize {
+        self.title.len()
+            + self.description.len()
+            + self
+                .fields
+                .iter()
+                .map(|field| field.name.len() + field.value.len())
+                .sum::<usize>()
+    }
 }
 
-async fn handle_player_left(auth_config: &ApiConfig, user_id: String, user: User) {
-    let avatar_file_id = extract_avatar_file_id(&user)
-        .unwrap_or(None)
-        .unwrap_or("".to_string());
-
-    send_embed(auth_config, user_id, |user| Embed {
-        title: "Player Left".into(),
-        description: format!("**{}** has left the instance", user.display_name),
-        fields: vec![
-            Field {
-                name: "User ID".into(),
-                value: user.id,
-                inline: false,
-            },
-            Field {
-                name: "Avatar File ID".into(),
-                value: avatar_file_id,
-                inline: false,
-            },
-        ],
-        thumbnail: Thumbnail {
-            url: user.current_avatar_thumbnail_image_url,
-        },
-        color: 0xFFA500,
-    })
-    .await;
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    username: String,
+    avatar_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    embeds: Vec<&'a Embed>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components: Option<&'a serde_json::Value>,
 }
 
-async fn send_embed<F>(auth_config: &ApiConfig, user_id: String, embed_builder: F)
-where
-    F: FnOnce(vrchatapi::models::User) -> Embed,
-{
-    let user = match users_api::get_user(auth_config, &user_id).await {
-        Ok(u) => u,
-        Err(e) => {
-            error!("Failed to fetch user {}: {}", user_id, e);
-            return;
+impl From<&Message> for Embed {
+    fn from(message: &Message) -> Self {
+        Self {
+            title: message.title.clone(),
+            description: message.description.clone(),
+            fields: message
+                .fields
+                .iter()
+                .map(|field| Field {
+                    name: field.name.clone(),
+                    value: field.value.clone(),
+                    inline: field.inline,
+                })
+                .collect(),
+            thumbnail: message.thumbnail.clone().map(|url| Thumbnail { url }),
+            color: message.color,
+            footer: message.footer.clone().map(|text| Footer { text }),
         }
-    };
-
-    let payload = WebhookPayload {
-        username: CONFIG.discord_webhook.username.clone(),
-        avatar_url: CONFIG.discord_webhook.avatar_url.clone(),
-        embeds: vec![embed_builder(user)],
-    };
-
-    let client = Client::new();
-    if let Err(e) = client
-        .post(&CONFIG.discord_webhook.url)
-        .json(&payload)
-        .send()
-        .await
-    {
-        error!("Webhook execution failed: {}", e);
     }
 }
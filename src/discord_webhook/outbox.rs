@@ -0,0 +1,436 @@
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Instant;
+use tracing::{error, warn};
+
+use super::routing::{TARGETS, WebhookTarget};
+use super::{Embed, WebhookPayload};
+use crate::config::CONFIG;
+use crate::storage::{Journal, Journaled};
+
+const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+const MAX_CHARS_PER_MESSAGE: usize = 6000;
+const COALESCE_WINDOW: Duration = Duration::from_secs(2);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct QueuedEmbed {
+    id: u64,
+    embed: Embed,
+    #[serde(default)]
+    components: Option<serde_json::Value>,
+    attempts: u32,
+}
+
+impl Journaled for QueuedEmbed {
+    type Id = u64;
+
+    fn id(&self) -> &u64 {
+        &self.id
+    }
+
+    fn attempts_mut(&mut self) -> &mut u32 {
+        &mut self.attempts
+    }
+}
+
+/// Queued embeds, with the ID the next one gets.
+struct Queue {
+    journal: Journal<QueuedEmbed>,
+    next_id: u64,
+}
+
+impl Queue {
+    fn load(file: String) -> Self {
+        let journal = Journal::<QueuedEmbed>::load(file);
+        let next_id = journal
+            .items()
+            .iter()
+            .map(|queued| queued.id + 1)
+            .max()
+            .unwrap_or(0);
+
+        Self { journal, next_id }
+    }
+
+    fn embeds(&self) -> &VecDeque<QueuedEmbed> {
+        self.journal.items()
+    }
+
+    fn push(&mut self, embed: Embed, components: Option<serde_json::Value>) {
+        let queued = QueuedEmbed {
+            id: self.next_id,
+            embed,
+            components,
+            attempts: 0,
+        };
+        self.next_id += 1;
+        self.journal.push(queued);
+    }
+
+    fn front_ids(&self, len: usize) -> Vec<u64> {
+        self.embeds()
+            .iter()
+            .take(len)
+            .map(|queued| queued.id)
+            .collect()
+    }
+
+    /// Removes the first `len` embeds, once they were sent or given up on.
+    fn remove_front(&mut self, len: usize) {
+        for id in self.front_ids(len) {
+            self.journal.remove(&id);
+        }
+    }
+
+    /// Counts a failed delivery against the first `len` embeds, dropping those out of attempts.
+    fn record_failure(&mut self, len: usize) {
+        let max_attempts = CONFIG
+            .discord_webhook
+            .max_attempts
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+
+        for id in self.front_ids(len) {
+            let attempts = self.journal.attempted(&id);
+            if max_attempts > 0 && attempts >= max_attempts {
+                if let Some(queued) = self.embeds().iter().find(|queued| queued.id == id) {
+                    error!(
+                        "Dropping Discord embed \"{}\" after {} attempts",
+                        queued.embed.title, attempts
+                    );
+                }
+                self.journal.remove(&id);
+            }
+        }
+    }
+}
+
+/// Each webhook has its own rate limit, so each target gets its own queue and worker.
+struct Outbox {
+    target: &'static WebhookTarget,
+    queue: Mutex<Queue>,
+    wake: Notify,
+    /// Nothing is sent on the webhook before this, queued or not.
+    blocked_until: Mutex<Option<Instant>>,
+}
+
+impl Outbox {
+    async fn block_for(&self, wait: Duration) {
+        let until = Instant::now() + wait;
+        let mut blocked_until = self.blocked_until.lock().await;
+        *blocked_until = Some(blocked_until.map_or(until, |current| current.max(until)));
+    }
+
+    async fn wait_for_bucket(&self) {
+        let blocked_until = *self.blocked_until.lock().await;
+        if let Some(until) = blocked_until {
+            tokio::time::sleep_until(until).await;
+        }
+    }
+}
+
+static OUTBOXES: Lazy<HashMap<&'static str, Outbox>> = Lazy::new(|| {
+    TARGETS
+        .iter()
+        .map(|target| {
+            let queue = Queue::load(format!("discord_outbox_{}.jsonl", target.name));
+            let outbox = Outbox {
+                target,
+                queue: Mutex::new(queue),
+                wake: Notify::new(),
+                blocked_until: Mutex::new(None),
+            };
+            (target.name.as_str(), outbox)
+        })
+        .collect()
+});
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+enum Delivery {
+    Sent,
+    RateLimited(Duration),
+    Failed(String),
+    Rejected(String),
+}
+
+#[derive(Deserialize)]
+struct RateLimitBody {
+    retry_after: f64,
+}
+
+pub async fn enqueue(target: &str, embed: Embed, components: Option<serde_json::Value>) {
+    let Some(outbox) = OUTBOXES.get(target) else {
+        error!("Unknown Discord webhook target: {}", target);
+        return;
+    };
+
+    outbox.queue.lock().await.push(embed, components);
+    outbox.wake.notify_one();
+}
+
+/// Counts how many queued embeds, from the front, fit into a single webhook message.
+/// Buttons belong to the whole message, so an embed that has some is always sent alone.
+fn batch_len(queue: &VecDeque<QueuedEmbed>) -> usize {
+    let mut len = 0;
+    let mut chars = 0;
+
+    for queued in queue.iter().take(MAX_EMBEDS_PER_MESSAGE) {
+        chars += queued.embed.char_count();
+        if len > 0 && (chars > MAX_CHARS_PER_MESSAGE || queued.components.is_some()) {
+            break;
+        }
+        if queued.components.is_some() {
+            return 1;
+        }
+        len += 1;
+    }
+
+    len
+}
+
+fn header_f64(response: &Response, name: &str) -> Option<f64> {
+    response.headers().get(name)?.to_str().ok()?.parse().ok()
+}
+
+/// Discord tells us up front when the bucket is exhausted, so wait before the next send.
+fn bucket_wait(response: &Response) -> Option<Duration> {
+    match header_f64(response, "x-ratelimit-remaining") {
+        Some(remaining) if remaining <= 0.0 => {
+            header_f64(response, "x-ratelimit-reset-after").map(Duration::from_secs_f64)
+        }
+        _ => None,
+    }
+}
+
+/// Sends a one-off request on a target's webhook, such as an edit, within the rate limit
+/// it shares with that target's queue. Rate limited requests are retried.
+pub async fn execute(target: &str, request: impl Fn() -> RequestBuilder) -> Result<Response> {
+    let outbox = OUTBOXES
+        .get(target)
+        .with_context(|| format!("Unknown Discord webhook target: {target}"))?;
+
+    loop {
+        outbox.wait_for_bucket().await;
+
+        let response = request()
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+
+        if let Some(wait) = bucket_wait(&response) {
+            outbox.block_for(wait).await;
+        }
+
+        if response.status() != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(response);
+        }
+
+        let retry_after = header_f64(&response, "retry-after")
+            .map(Duration::from_secs_f64)
+            .unwrap_or(MIN_BACKOFF);
+        warn!(
+            "Discord rate limit hit on {}, retrying in {:.1}s",
+            target,
+            retry_after.as_secs_f64()
+        );
+        outbox.block_for(retry_after).await;
+    }
+}
+
+async fn deliver(url: &str, payload: &serde_json::Value) -> (Delivery, Option<Duration>) {
+    let response = match CLIENT.post(url).json(payload).send().await {
+        Ok(response) => response,
+        Err(e) => return (Delivery::Failed(e.without_url().to_string()), None),
+    };
+
+    let bucket_wait = bucket_wait(&response);
+
+    let status = response.status();
+    let delivery = if status.is_success() {
+        Delivery::Sent
+    } else if status == StatusCode::TOO_MANY_REQUESTS {
+        let header_wait = header_f64(&response, "retry-after").map(Duration::from_secs_f64);
+        let body_wait = response
+            .json::<RateLimitBody>()
+            .await
+            .ok()
+            .map(|body| Duration::from_secs_f64(body.retry_after));
+
+        Delivery::RateLimited(body_wait.or(header_wait).unwrap_or(MIN_BACKOFF))
+    } else if status.is_server_error() {
+        Delivery::Failed(format!("HTTP {status}"))
+    } else {
+        let body = response.text().await.unwrap_or_default();
+        Delivery::Rejected(format!("HTTP {status}: {body}"))
+    };
+
+    (delivery, bucket_wait)
+}
+
+async fn run(outbox: &'static Outbox) {
+    let target = outbox.target;
+    let mut backoff = MIN_BACKOFF;
+    // After a batch is rejected its embeds are retried one by one, so only the bad one is dropped.
+    let mut send_alone = 0;
+
+    loop {
+        if outbox.queue.lock().await.embeds().is_empty() {
+            outbox.wake.notified().await;
+            // Give bursts of events a moment to pile up so they share a message.
+            tokio::time::sleep(COALESCE_WINDOW).await;
+        }
+
+        // The batch stays in the queue (and on disk) until Discord has accepted it.
+        let (len, payload) = {
+            let queue = outbox.queue.lock().await;
+            let queue = queue.embeds();
+            let len = if send_alone > 0 {
+                batch_len(queue).min(1)
+            } else {
+                batch_len(queue)
+            };
+            let payload = WebhookPayload {
+                username: target.username(),
+                avatar_url: target.avatar_url(),
+                content: target.mention.clone(),
+                embeds: queue.iter().take(len).map(|queued| &queued.embed).collect(),
+                components: queue.front().and_then(|queued| queued.components.as_ref()),
+            };
+            (len, serde_json::to_value(&payload))
+        };
+
+        if len == 0 {
+            continue;
+        }
+
+        let payload = match payload {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!(
+                    "Failed to serialize webhook payload, dropping message: {}",
+                    e
+                );
+                outbox.queue.lock().await.remove_front(len);
+                continue;
+            }
+        };
+
+        outbox.wait_for_bucket().await;
+        let (delivery, bucket_wait) = deliver(&target.url, &payload).await;
+        if let Some(wait) = bucket_wait {
+            outbox.block_for(wait).await;
+        }
+
+        match delivery {
+            Delivery::Sent => {
+                backoff = MIN_BACKOFF;
+                send_alone -= len.min(send_alone);
+                outbox.queue.lock().await.remove_front(len);
+            }
+            Delivery::RateLimited(retry_after) => {
+                // Rate limiting isn't the message's fault, so it doesn't count as an attempt.
+                warn!(
+                    "Discord rate limit hit on {}, retrying in {:.1}s",
+                    target.name,
+                    retry_after.as_secs_f64()
+                );
+                outbox.block_for(retry_after).await;
+            }
+            Delivery::Failed(reason) => {
+                warn!(
+                    "Webhook execution on {} failed, retrying in {}s: {}",
+                    target.name,
+                    backoff.as_secs(),
+                    reason
+                );
+                outbox.queue.lock().await.record_failure(len);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Delivery::Rejected(reason) if len > 1 => {
+                warn!(
+                    "Webhook execution on {} rejected, retrying its {} embeds one by one: {}",
+                    target.name, len, reason
+                );
+                send_alone = len;
+            }
+            Delivery::Rejected(reason) => {
+                send_alone -= len.min(send_alone);
+                error!(
+                    "Webhook execution on {} rejected, dropping message: {}",
+                    target.name, reason
+                );
+                outbox.queue.lock().await.remove_front(len);
+            }
+        }
+    }
+}
+
+pub fn start() {
+    for outbox in OUTBOXES.values() {
+        tokio::spawn(run(outbox));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(description_len: usize, components: bool) -> QueuedEmbed {
+        QueuedEmbed {
+            id: 0,
+            embed: Embed {
+                title: String::new(),
+                description: "x".repeat(description_len),
+                fields: Vec::new(),
+                thumbnail: None,
+                color: 0,
+                footer: None,
+            },
+            components: components.then(|| serde_json::json!([])),
+            attempts: 0,
+        }
+    }
+
+    fn queue(embeds: impl IntoIterator<Item = QueuedEmbed>) -> VecDeque<QueuedEmbed> {
+        embeds.into_iter().collect()
+    }
+
+    #[test]
+    fn caps_embeds_per_message() {
+        assert_eq!(batch_len(&queue([])), 0);
+        assert_eq!(batch_len(&queue((0..3).map(|_| queued(10, false)))), 3);
+        assert_eq!(batch_len(&queue((0..15).map(|_| queued(10, false)))), 10);
+    }
+
+    #[test]
+    fn caps_characters_per_message() {
+        let exact = queue([queued(3000, false), queued(3000, false)]);
+        assert_eq!(batch_len(&exact), 2);
+
+        let over = queue([queued(3000, false), queued(3000, false), queued(1, false)]);
+        assert_eq!(batch_len(&over), 2);
+    }
+
+    #[test]
+    fn sends_oversized_first_embed_alone() {
+        let oversized = queue([queued(7000, false), queued(10, false)]);
+        assert_eq!(batch_len(&oversized), 1);
+    }
+
+    #[test]
+    fn sends_embeds_with_components_alone() {
+        let first = queue([queued(10, true), queued(10, false)]);
+        assert_eq!(batch_len(&first), 1);
+
+        let later = queue([queued(10, false), queued(10, false), queued(10, true)]);
+        assert_eq!(batch_len(&later), 2);
+    }
+}
@@ -0,0 +1,420 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local, Utc};
+use once_cell::sync::Lazy;
+use reqwest::{Client, StatusCode, Url};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+use super::outbox;
+use super::routing::{TARGETS, WebhookTarget};
+use super::{Embed, Field, Footer, WebhookPayload};
+use crate::events::{AppEvent, UserSnapshot};
+use crate::notifiers::message::format_duration;
+use crate::{config::CONFIG, listen};
+
+const MIN_UPDATE_INTERVAL: u64 = 5;
+/// Even without joins or leaves, the session duration goes stale, so refresh it now and then.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_DESCRIPTION_CHARS: usize = 4000;
+const MAX_FIELD_CHARS: usize = 1000;
+
+struct Player {
+    display_name: String,
+    joined_at: DateTime<Utc>,
+}
+
+struct Flag {
+    display_name: String,
+    reason: String,
+}
+
+/// One instance visit, mirrored by a single Discord message.
+struct Session {
+    location: String,
+    started_at: DateTime<Utc>,
+    ended_at: Option<DateTime<Utc>>,
+    message_id: Option<String>,
+    players: HashMap<String, Player>,
+    /// Players the log saw join this session, enriched joins of anyone else are stale.
+    present: HashSet<String>,
+    flagged: BTreeMap<String, Flag>,
+    peak: usize,
+    dirty: bool,
+    updated_at: Instant,
+}
+
+impl Session {
+    fn new(location: String) -> Self {
+        Self {
+            location,
+            started_at: Utc::now(),
+            ended_at: None,
+            message_id: None,
+            players: HashMap::new(),
+            present: HashSet::new(),
+            flagged: BTreeMap::new(),
+            peak: 0,
+            dirty: true,
+            updated_at: Instant::now(),
+        }
+    }
+
+    fn flag(&mut self, snapshot: &UserSnapshot, reason: String) {
+        self.flagged.insert(
+            snapshot.id().to_string(),
+            Flag {
+                display_name: snapshot.user.display_name.clone(),
+                reason,
+            },
+        );
+        self.dirty = true;
+    }
+}
+
+/// The session being shown, and ended ones whose final state hasn't been pushed yet.
+#[derive(Default)]
+struct Sessions {
+    current: Option<Session>,
+    ended: VecDeque<Session>,
+}
+
+impl Sessions {
+    fn end_current(&mut self) {
+        if let Some(mut session) = self.current.take() {
+            session.ended_at = Some(Utc::now());
+            self.ended.push_back(session);
+        }
+    }
+
+    fn find(&mut self, started_at: DateTime<Utc>) -> Option<&mut Session> {
+        self.current
+            .iter_mut()
+            .chain(self.ended.iter_mut())
+            .find(|session| session.started_at == started_at)
+    }
+}
+
+#[derive(Deserialize)]
+struct Message {
+    id: String,
+}
+
+static SESSIONS: Lazy<Mutex<Sessions>> = Lazy::new(|| Mutex::new(Sessions::default()));
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// Joins lines until `limit` is reached, summarising whatever doesn't fit.
+fn join_lines(lines: Vec<String>, limit: usize) -> String {
+    let mut text = String::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        if text.len() + line.len() + 1 > limit {
+            text.push_str(&format!("…and {} more", lines.len() - index));
+            break;
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    text
+}
+
+fn render(session: &Session) -> Embed {
+    let now = session.ended_at.unwrap_or_else(Utc::now);
+
+    let mut players = session.players.values().collect::<Vec<_>>();
+    players.sort_by_key(|player| player.joined_at);
+
+    let lines = players
+        .iter()
+        .map(|player| {
+            format!(
+                "**{}** · {}",
+                player.display_name,
+                format_duration(now - player.joined_at)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let description = if lines.is_empty() {
+        "*No players*".to_string()
+    } else {
+        join_lines(lines, MAX_DESCRIPTION_CHARS)
+    };
+
+    let mut fields = vec![
+        Field {
+            name: "Players".into(),
+            value: format!("{} (peak {})", session.players.len(), session.peak),
+            inline: true,
+        },
+        Field {
+            name: "Session".into(),
+            value: format_duration(now - session.started_at),
+            inline: true,
+        },
+        Field {
+            name: "Instance".into(),
+            value: format!("`{}`", session.location),
+            inline: false,
+        },
+    ];
+
+    if !session.flagged.is_empty() {
+        let lines = session
+            .flagged
+            .iter()
+            .map(|(user_id, flag)| {
+                let presence = if session.players.contains_key(user_id) {
+                    ""
+                } else {
+                    " (left)"
+                };
+                format!("**{}**{}: {}", flag.display_name, presence, flag.reason)
+            })
+            .collect::<Vec<_>>();
+
+        fields.push(Field {
+            name: format!("Flagged ({})", session.flagged.len()),
+            value: join_lines(lines, MAX_FIELD_CHARS),
+            inline: false,
+        });
+    }
+
+    let (title, color, footer) = match session.ended_at {
+        Some(ended_at) => (
+            "Instance Roster (ended)",
+            0x808080,
+            format!(
+                "Session ended {}",
+                ended_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+            ),
+        ),
+        None => (
+            "Instance Roster",
+            if session.flagged.is_empty() {
+                0x00FF00
+            } else {
+                0xFF0000
+            },
+            format!("Last updated {}", Local::now().format("%Y-%m-%d %H:%M:%S")),
+        ),
+    };
+
+    Embed {
+        title: title.into(),
+        description,
+        fields,
+        thumbnail: None,
+        color,
+        footer: Some(Footer { text: footer }),
+    }
+}
+
+async fn post(target: &WebhookTarget, embed: &Embed) -> Result<String> {
+    let mut url = Url::parse(&target.url).context("Invalid webhook URL")?;
+    url.query_pairs_mut().append_pair("wait", "true");
+
+    let payload = WebhookPayload {
+        username: target.username(),
+        avatar_url: target.avatar_url(),
+        content: None,
+        embeds: vec![embed],
+        components: None,
+    };
+
+    let message = outbox::execute(&target.name, || CLIENT.post(url.clone()).json(&payload))
+        .await?
+        .error_for_status()
+        .map_err(reqwest::Error::without_url)?
+        .json::<Message>()
+        .await
+        .map_err(reqwest::Error::without_url)?;
+
+    Ok(message.id)
+}
+
+/// Returns false when the message no longer exists, e.g. because someone deleted it.
+async fn edit(target: &WebhookTarget, message_id: &str, embed: &Embed) -> Result<bool> {
+    let mut url = Url::parse(&target.url).context("Invalid webhook URL")?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Invalid webhook URL"))?
+        .extend(["messages", message_id]);
+
+    let body = serde_json::json!({ "embeds": [embed] });
+    let response = outbox::execute(&target.name, || CLIENT.patch(url.clone()).json(&body)).await?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+
+    response
+        .error_for_status()
+        .map_err(reqwest::Error::without_url)?;
+    Ok(true)
+}
+
+/// Edits the session's message, or posts a new one. Returns the message's ID.
+async fn send(target: &WebhookTarget, message_id: Option<String>, embed: &Embed) -> Result<String> {
+    if let Some(message_id) = message_id
+        && edit(target, &message_id, embed).await?
+    {
+        return Ok(message_id);
+    }
+
+    post(target, embed).await
+}
+
+/// Pushes the final state of sessions that have ended.
+async fn finalize_ended(target: &WebhookTarget) {
+    loop {
+        let Some(session) = SESSIONS.lock().await.ended.pop_front() else {
+            return;
+        };
+
+        // Nothing was ever posted for a session we only passed through.
+        if session.message_id.is_none() && session.peak == 0 {
+            continue;
+        }
+
+        if let Err(e) = send(target, session.message_id.clone(), &render(&session)).await {
+            error!("Failed to finalize roster message: {:#}", e);
+        }
+    }
+}
+
+/// Pushes the current session. The lock is only held to render it, not while sending.
+async fn update_current(target: &WebhookTarget) -> Result<()> {
+    let (embed, message_id, started_at) = {
+        let mut sessions = SESSIONS.lock().await;
+        let Some(session) = sessions.current.as_mut() else {
+            return Ok(());
+        };
+
+        if !session.dirty && session.updated_at.elapsed() < REFRESH_INTERVAL {
+            return Ok(());
+        }
+
+        // Changes made while sending mark it dirty again.
+        session.dirty = false;
+        session.updated_at = Instant::now();
+        (
+            render(session),
+            session.message_id.clone(),
+            session.started_at,
+        )
+    };
+
+    let result = send(target, message_id, &embed).await;
+
+    // The session may have ended meanwhile, its final edit still needs the message ID.
+    let mut sessions = SESSIONS.lock().await;
+    let Some(session) = sessions.find(started_at) else {
+        return result.map(|_| ());
+    };
+
+    match result {
+        Ok(message_id) => {
+            session.message_id = Some(message_id);
+            Ok(())
+        }
+        Err(e) => {
+            session.dirty = true;
+            Err(e)
+        }
+    }
+}
+
+/// All roster HTTP requests happen here, one at a time.
+async fn run(target: &'static WebhookTarget) {
+    let interval = CONFIG
+        .discord_webhook
+        .roster
+        .update_interval
+        .max(MIN_UPDATE_INTERVAL);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        finalize_ended(target).await;
+
+        // A failed update stays dirty, so the next tick simply tries again.
+        if let Err(e) = update_current(target).await {
+            warn!("Failed to update roster message: {:#}", e);
+        }
+    }
+}
+
+pub fn init() {
+    let settings = &CONFIG.discord_webhook.roster;
+
+    let target = match &settings.target {
+        Some(name) => TARGETS.iter().find(|target| &target.name == name),
+        None => TARGETS.first(),
+    };
+
+    let Some(target) = target else {
+        error!(
+            "Roster webhook target {} not found",
+            settings.target.as_deref().unwrap_or_default()
+        );
+        return;
+    };
+
+    tokio::spawn(run(target));
+
+    listen!(
+        AppEvent::OnInstanceJoined(location) => {
+            let mut sessions = SESSIONS.lock().await;
+            sessions.end_current();
+            sessions.current = Some(Session::new(location));
+        },
+        AppEvent::OnInstanceLeft => {
+            SESSIONS.lock().await.end_current();
+        },
+        AppEvent::OnPlayerJoinedRaw(user_id) => {
+            if let Some(session) = SESSIONS.lock().await.current.as_mut() {
+                session.present.insert(user_id);
+            }
+        },
+        AppEvent::OnPlayerLeftRaw(user_id) => {
+            if let Some(session) = SESSIONS.lock().await.current.as_mut() {
+                session.present.remove(&user_id);
+            }
+        },
+        AppEvent::OnPlayerJoined(snapshot) => {
+            // Joins are enriched with an API call, so one may arrive after we left its instance.
+            if let Some(session) = SESSIONS.lock().await.current.as_mut()
+                && session.present.contains(snapshot.id())
+            {
+                session.players.insert(
+                    snapshot.id().to_string(),
+                    Player {
+                        display_name: snapshot.user.display_name.clone(),
+                        joined_at: snapshot.captured_at,
+                    },
+                );
+                session.peak = session.peak.max(session.players.len());
+                session.dirty = true;
+            }
+        },
+        AppEvent::OnPlayerLeft(snapshot) => {
+            if let Some(session) = SESSIONS.lock().await.current.as_mut() {
+                session.players.remove(snapshot.id());
+                session.dirty = true;
+            }
+        },
+        AppEvent::OnWatchlistHit(snapshot) => {
+            if let Some(session) = SESSIONS.lock().await.current.as_mut() {
+                session.flag(&snapshot, "Watchlisted".into());
+            }
+        },
+        AppEvent::OnAutoBanned(snapshot, reason) => {
+            if let Some(session) = SESSIONS.lock().await.current.as_mut() {
+                session.flag(&snapshot, reason);
+            }
+        }
+    );
+}
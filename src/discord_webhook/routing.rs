@@ -0,0 +1,46 @@
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::config::CONFIG;
+use crate::notifiers::routing::Filter;
+
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct WebhookTarget {
+    pub name: String,
+    pub url: String,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+    pub mention: Option<String>,
+    #[serde(flatten)]
+    pub filter: Filter,
+}
+
+impl WebhookTarget {
+    pub fn username(&self) -> String {
+        self.username
+            .clone()
+            .unwrap_or_else(|| CONFIG.discord_webhook.username.clone())
+    }
+
+    pub fn avatar_url(&self) -> String {
+        self.avatar_url
+            .clone()
+            .unwrap_or_else(|| CONFIG.discord_webhook.avatar_url.clone())
+    }
+}
+
+/// Configured targets, falling back to the top-level `url` as a single catch-all target.
+pub static TARGETS: Lazy<Vec<WebhookTarget>> = Lazy::new(|| {
+    let settings = &CONFIG.discord_webhook;
+
+    if !settings.targets.is_empty() {
+        return settings.targets.clone();
+    }
+
+    vec![WebhookTarget {
+        name: "default".into(),
+        url: settings.url.clone(),
+        ..Default::default()
+    }]
+});
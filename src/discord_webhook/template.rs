@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::notifiers::message::{Field, Message};
+
+pub type Vars = HashMap<&'static str, String>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FieldTemplate {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub inline: bool,
+}
+
+/// Overrides for a single event's message, on Discord and every other notifier.
+/// Anything left unset keeps the built-in wording.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct EmbedTemplate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub color: Option<u32>,
+    pub thumbnail: Option<String>,
+    pub footer: Option<String>,
+    pub fields: Option<Vec<FieldTemplate>>,
+}
+
+/// Replaces every `{name}` placeholder with its value. Unknown placeholders are left as-is,
+/// and substituted values are never expanded again.
+pub fn fill(text: &str, vars: &Vars) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest
+            .find('}')
+            .and_then(|end| Some((vars.get(&rest[1..end])?, end)));
+        match value {
+            Some((value, end)) => {
+                filled.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    filled.push_str(rest);
+    filled
+}
+
+impl EmbedTemplate {
+    pub fn apply(&self, mut message: Message) -> Message {
+        let vars = &message.vars;
+
+        if let Some(title) = &self.title {
+            message.title = fill(title, vars);
+        }
+
+        if let Some(description) = &self.description {
+            message.description = fill(description, vars);
+        }
+
+        if let Some(color) = self.color {
+            message.color = color;
+        }
+
+        if let Some(thumbnail) = &self.thumbnail {
+            let url = fill(thumbnail, vars);
+            message.thumbnail = (!url.is_empty()).then_some(url);
+        }
+
+        if let Some(footer) = &self.footer {
+            let text = fill(footer, vars);
+            message.footer = (!text.is_empty()).then_some(text);
+        }
+
+        if let Some(fields) = &self.fields {
+            message.fields = fields
+                .iter()
+                .map(|field| Field {
+                    name: fill(&field.name, vars),
+                    value: fill(&field.value, vars),
+                    inline: field.inline,
+                })
+                .collect();
+        }
+
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Vars {
+        Vars::from([
+            ("display_name", "Alice".to_string()),
+            ("reason", "{display_name} said {user_id}".to_string()),
+        ])
+    }
+
+    #[test]
+    fn fills_known_placeholders() {
+        assert_eq!(fill("{display_name} joined", &vars()), "Alice joined");
+        assert_eq!(
+            fill("{display_name}/{display_name}", &vars()),
+            "Alice/Alice"
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_placeholders() {
+        assert_eq!(fill("{unknown} {display_name}", &vars()), "{unknown} Alice");
+        assert_eq!(fill("{}", &vars()), "{}");
+    }
+
+    #[test]
+    fn never_expands_substituted_values() {
+        assert_eq!(
+            fill("Reason: {reason}", &vars()),
+            "Reason: {display_name} said {user_id}"
+        );
+    }
+
+    #[test]
+    fn handles_stray_braces() {
+        assert_eq!(fill("{{display_name}}", &vars()), "{Alice}");
+        assert_eq!(fill("open { brace", &vars()), "open { brace");
+        assert_eq!(fill("close } brace", &vars()), "close } brace");
+        assert_eq!(
+            fill("ends with {display_name", &vars()),
+            "ends with {display_name"
+        );
+        assert_eq!(fill("héllo {display_name} ✓", &vars()), "héllo Alice ✓");
+    }
+}
@@ -0,0 +1,208 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tracing::{error, warn};
+
+use crate::config::CONFIG;
+use crate::events::EVENT_BUS;
+use crate::events::schema::Envelope;
+use crate::storage::{Journal, Journaled};
+
+const OUTBOX_FILE: &str = "event_webhook_outbox.jsonl";
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+/// While the receiver is down, the oldest events are dropped beyond this many.
+const MAX_QUEUED: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct QueuedEvent {
+    id: String,
+    event: String,
+    body: String,
+    attempts: u32,
+}
+
+impl Journaled for QueuedEvent {
+    type Id = String;
+
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    fn attempts_mut(&mut self) -> &mut u32 {
+        &mut self.attempts
+    }
+}
+
+enum Delivery {
+    Sent,
+    Failed(String, Option<Duration>),
+    Rejected(String),
+}
+
+static OUTBOX: Lazy<Mutex<Journal<QueuedEvent>>> =
+    Lazy::new(|| Mutex::new(Journal::load(OUTBOX_FILE.to_string())));
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// Signs `{timestamp}.{body}`, so a captured request can't be replayed with a new timestamp.
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(body.as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+async fn deliver(secret: &str, queued: &QueuedEvent) -> Delivery {
+    let timestamp = Utc::now().timestamp();
+
+    let request = CLIENT
+        .post(&CONFIG.event_webhook.url)
+        .header("Content-Type", "application/json")
+        .header("X-VRCManager-Event", &queued.event)
+        .header("X-VRCManager-Delivery", &queued.id)
+        .header("X-VRCManager-Timestamp", timestamp.to_string())
+        .header(
+            "X-VRCManager-Signature",
+            sign(secret, timestamp, &queued.body),
+        );
+
+    let response = match request.body(queued.body.clone()).send().await {
+        Ok(response) => response,
+        Err(e) => return Delivery::Failed(e.without_url().to_string(), None),
+    };
+
+    let status = response.status();
+    if status.is_success() {
+        return Delivery::Sent;
+    }
+
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::REQUEST_TIMEOUT {
+        let retry_after = response
+            .headers()
+            .get("retry-after")
+            .and_then(|value| value.to_str().ok()?.parse().ok())
+            .map(Duration::from_secs);
+        return Delivery::Failed(format!("HTTP {status}"), retry_after);
+    }
+
+    if status.is_server_error() {
+        return Delivery::Failed(format!("HTTP {status}"), None);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    Delivery::Rejected(format!("HTTP {status}: {body}"))
+}
+
+async fn run(secret: &'static str) {
+    let max_attempts = CONFIG
+        .event_webhook
+        .max_attempts
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        // Events stay queued (and on disk) until the receiver has accepted them, in order.
+        let front = OUTBOX.lock().await.items().front().cloned();
+
+        let Some(queued) = front else {
+            WAKE.notified().await;
+            continue;
+        };
+
+        match deliver(secret, &queued).await {
+            Delivery::Sent => {
+                backoff = MIN_BACKOFF;
+                OUTBOX.lock().await.remove(&queued.id);
+            }
+            Delivery::Failed(reason, retry_after) => {
+                let wait = retry_after.unwrap_or(backoff);
+                warn!(
+                    "Event webhook delivery of {} failed, retrying in {}s: {}",
+                    queued.id,
+                    wait.as_secs(),
+                    reason
+                );
+
+                {
+                    let mut outbox = OUTBOX.lock().await;
+                    let attempts = outbox.attempted(&queued.id);
+                    if max_attempts > 0 && attempts >= max_attempts {
+                        error!("Dropping event {} after {} attempts", queued.id, attempts);
+                        outbox.remove(&queued.id);
+                    }
+                }
+
+                tokio::time::sleep(wait).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Delivery::Rejected(reason) => {
+                error!(
+                    "Event webhook rejected {}, dropping it: {}",
+                    queued.id, reason
+                );
+                OUTBOX.lock().await.remove(&queued.id);
+            }
+        }
+    }
+}
+
+pub fn init() {
+    let settings = &CONFIG.event_webhook;
+
+    // Receivers have no other way to tell our requests from forged ones.
+    let Some(secret) = settings
+        .secret
+        .as_deref()
+        .filter(|secret| !secret.is_empty())
+    else {
+        error!("Event webhook disabled: set event_webhook.secret");
+        return;
+    };
+
+    let events = &settings.events;
+    tokio::spawn(run(secret));
+
+    tokio::spawn(async move {
+        let mut rx = EVENT_BUS.subscribe().await;
+
+        while let Some(event) = rx.recv().await {
+            let envelope = Envelope::new(&event);
+            let name = envelope.data.name();
+
+            if !events.is_empty() && !events.iter().any(|event| event == name) {
+                continue;
+            }
+
+            let body = match serde_json::to_string(&envelope) {
+                Ok(body) => body,
+                Err(e) => {
+                    error!("Failed to serialize {} event: {}", name, e);
+                    continue;
+                }
+            };
+
+            let mut outbox = OUTBOX.lock().await;
+            outbox.push(QueuedEvent {
+                id: envelope.id,
+                event: name.to_string(),
+                body,
+                attempts: 0,
+            });
+
+            if outbox.items().len() > MAX_QUEUED {
+                outbox.pop_front();
+                warn!("Event webhook outbox is full, dropped the oldest event");
+            }
+            WAKE.notify_one();
+        }
+    });
+}
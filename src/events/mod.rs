@@ -1,21 +1,85 @@
+use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::{Mutex, mpsc};
 use vrchatapi::models::User;
 
+use crate::blocklist::BlocklistChange;
+use crate::blocklist::history::BlocklistVersion;
+use crate::session_report::{SessionReport, WeeklyDigest};
+use crate::vrchat::ban_approval::PendingBan;
+use crate::vrchat::group_audit::GroupActivity;
+use crate::vrchat::util::extract_avatar_file_id;
+
+pub mod schema;
+
 type EventSender = mpsc::UnboundedSender<AppEvent>;
 type EventReceiver = mpsc::UnboundedReceiver<AppEvent>;
 
+/// A user as they were at the moment an event happened, so every consumer sees
+/// the same avatar without fetching the user again.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UserSnapshot {
+    pub user: User,
+    pub avatar_file_id: Option<String>,
+    pub captured_at: DateTime<Utc>,
+}
+
+impl UserSnapshot {
+    pub fn new(user: User) -> Self {
+        let avatar_file_id = extract_avatar_file_id(&user).unwrap_or(None);
+
+        Self {
+            user,
+            avatar_file_id,
+            captured_at: Utc::now(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.user.id
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone)]
 pub enum AppEvent {
     OnPlayerJoinedRaw(String),
     OnPlayerLeftRaw(String),
     OnAvatarChangedRaw(String),
-    OnPlayerJoined(String, User),
-    OnPlayerLeft(String, User),
-    OnAvatarChanged(String, User),
-    OnAutoBanned(String, String), // user_id, avatar_file_id
-    OnAutoInvited(String),
+    OnPlayerJoined(UserSnapshot),
+    OnPlayerLeft(UserSnapshot),
+    OnAvatarChanged(UserSnapshot),
+    OnAutoBanned(UserSnapshot, String), // snapshot, reason
+    OnAutoInvited(UserSnapshot),
+    OnInviteQuotaReached(String), // limit
+    OnJoinRequestAccepted(UserSnapshot),
+    OnJoinRequestRejected(UserSnapshot, String), // snapshot, reason
+    OnJoinRequestEscalated(UserSnapshot, String), // snapshot, reason
+    OnRoleGranted(UserSnapshot, String),         // snapshot, role_id
+    OnRoleRevoked(UserSnapshot, String),         // snapshot, role_id
+    OnWatchlistHit(UserSnapshot),
+    OnInstanceJoined(String), // location
+    OnInstanceLeft,
+    OnSessionEnded(SessionReport),
+    OnWeeklyDigest(WeeklyDigest),
+    OnAutomationChanged(Vec<String>), // paused automations
+    OnBanPending(PendingBan),
+    OnBanDiscarded(PendingBan, String), // pending ban, who discarded it
+    OnBanFailed(PendingBan, String),    // pending ban, why it couldn't be carried out
+    OnGroupMemberJoined(GroupActivity),
+    OnGroupMemberLeft(GroupActivity),
+    OnGroupMemberRemoved(GroupActivity),
+    OnGroupUserBanned(GroupActivity),
+    OnGroupUserUnbanned(GroupActivity),
+    OnGroupRoleAssigned(GroupActivity),
+    OnGroupRoleUnassigned(GroupActivity),
+    OnGroupPostCreated(GroupActivity),
+    OnGroupInstanceCreated(GroupActivity),
+    OnBlocklistAlert(UserSnapshot, String), // snapshot, reason
+    OnBlocklistUpdated(BlocklistChange),
+    OnBlocklistRolledBack(BlocklistVersion),
 }
 
 pub static EVENT_BUS: Lazy<Arc<EventBus>> = Lazy::new(|| Arc::new(EventBus::new()));
@@ -0,0 +1,512 @@
+//! The public JSON representation of events, for sinks other than Discord.
+//!
+//! Fields are only ever added within a schema version. Renaming or removing anything,
+//! or changing a field's meaning, requires bumping `SCHEMA_VERSION`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{AppEvent, UserSnapshot};
+use crate::blocklist::BlocklistChange;
+use crate::blocklist::history::BlocklistVersion;
+use crate::session_report::{SessionReport, WeeklyDigest};
+use crate::vrchat::ban_approval::PendingBan;
+use crate::vrchat::group_audit::GroupActivity;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UserData {
+    pub id: String,
+    pub display_name: String,
+    pub avatar_file_id: Option<String>,
+    pub avatar_thumbnail_url: String,
+    pub captured_at: DateTime<Utc>,
+}
+
+impl From<&UserSnapshot> for UserData {
+    fn from(snapshot: &UserSnapshot) -> Self {
+        Self {
+            id: snapshot.user.id.clone(),
+            display_name: snapshot.user.display_name.clone(),
+            avatar_file_id: snapshot.avatar_file_id.clone(),
+            avatar_thumbnail_url: snapshot.user.current_avatar_thumbnail_image_url.clone(),
+            captured_at: snapshot.captured_at,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PendingBanData {
+    pub id: u64,
+    pub user: UserData,
+    pub reason: String,
+    pub evidence_id: Option<u64>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<&PendingBan> for PendingBanData {
+    fn from(ban: &PendingBan) -> Self {
+        Self {
+            id: ban.id,
+            user: (&ban.snapshot).into(),
+            reason: ban.reason.clone(),
+            evidence_id: ban.evidence_id,
+            created_at: ban.created_at,
+            expires_at: ban.expires_at,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionReportData {
+    pub location: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub peak_players: usize,
+    pub average_players: f64,
+    pub unique_players: usize,
+    pub new_players: usize,
+    pub returning_players: usize,
+    pub avatar_switches: u32,
+    pub bans: u32,
+    pub invites: u32,
+    pub watchlist_hits: Vec<String>,
+}
+
+impl From<&SessionReport> for SessionReportData {
+    fn from(report: &SessionReport) -> Self {
+        Self {
+            location: report.location.clone(),
+            started_at: report.started_at,
+            ended_at: report.ended_at,
+            peak_players: report.peak_players,
+            average_players: report.average_players,
+            unique_players: report.unique_players,
+            new_players: report.new_players,
+            returning_players: report.returning_players,
+            avatar_switches: report.avatar_switches,
+            bans: report.bans,
+            invites: report.invites,
+            watchlist_hits: report.watchlist_hits.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WeeklyDigestData {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub sessions: usize,
+    pub locations: usize,
+    pub time_in_instances_seconds: i64,
+    pub peak_players: usize,
+    pub players_met: usize,
+    pub new_players: usize,
+    pub avatar_switches: u32,
+    pub bans: u32,
+    pub invites: u32,
+    pub watchlist_hits: Vec<String>,
+}
+
+impl From<&WeeklyDigest> for WeeklyDigestData {
+    fn from(digest: &WeeklyDigest) -> Self {
+        Self {
+            since: digest.since,
+            until: digest.until,
+            sessions: digest.sessions,
+            locations: digest.locations,
+            time_in_instances_seconds: digest.time_in_instances.num_seconds(),
+            peak_players: digest.peak_players,
+            players_met: digest.players_met,
+            new_players: digest.new_players,
+            avatar_switches: digest.avatar_switches,
+            bans: digest.bans,
+            invites: digest.invites,
+            watchlist_hits: digest.watchlist_hits.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GroupActivityData {
+    pub id: String,
+    pub event_type: String,
+    pub actor_id: Option<String>,
+    pub actor_display_name: Option<String>,
+    pub target_id: Option<String>,
+    pub description: String,
+    /// Passed through from VRChat as it is.
+    pub data: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    pub by_manager: bool,
+}
+
+impl From<&GroupActivity> for GroupActivityData {
+    fn from(activity: &GroupActivity) -> Self {
+        Self {
+            id: activity.id.clone(),
+            event_type: activity.event_type.clone(),
+            actor_id: activity.actor_id.clone(),
+            actor_display_name: activity.actor_display_name.clone(),
+            target_id: activity.target_id.clone(),
+            description: activity.description.clone(),
+            data: activity.data.clone(),
+            created_at: activity.created_at,
+            by_manager: activity.by_manager,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BlocklistChangeData {
+    pub source: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl From<&BlocklistChange> for BlocklistChangeData {
+    fn from(change: &BlocklistChange) -> Self {
+        Self {
+            source: change.source.clone(),
+            added: change.added.clone(),
+            removed: change.removed.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BlocklistVersionData {
+    pub version: u64,
+    pub author: String,
+    pub reason: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub rollback_to: Option<u64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<&BlocklistVersion> for BlocklistVersionData {
+    fn from(version: &BlocklistVersion) -> Self {
+        Self {
+            version: version.version,
+            author: version.author.clone(),
+            reason: version.reason.clone(),
+            added: version.added.clone(),
+            removed: version.removed.clone(),
+            rollback_to: version.rollback_to,
+            timestamp: version.timestamp,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum EventData {
+    PlayerJoinedRaw {
+        user_id: String,
+    },
+    PlayerLeftRaw {
+        user_id: String,
+    },
+    AvatarChangedRaw {
+        user_id: String,
+    },
+    PlayerJoined {
+        user: UserData,
+    },
+    PlayerLeft {
+        user: UserData,
+    },
+    AvatarChanged {
+        user: UserData,
+    },
+    #[serde(rename = "auto_ban")]
+    AutoBanned {
+        user: UserData,
+        reason: String,
+    },
+    #[serde(rename = "auto_invite")]
+    AutoInvited {
+        user: UserData,
+    },
+    #[serde(rename = "invite_quota")]
+    InviteQuotaReached {
+        limit: String,
+    },
+    JoinRequestAccepted {
+        user: UserData,
+    },
+    JoinRequestRejected {
+        user: UserData,
+        reason: String,
+    },
+    JoinRequestEscalated {
+        user: UserData,
+        reason: String,
+    },
+    RoleGranted {
+        user: UserData,
+        role_id: String,
+    },
+    RoleRevoked {
+        user: UserData,
+        role_id: String,
+    },
+    WatchlistHit {
+        user: UserData,
+    },
+    InstanceJoined {
+        location: String,
+    },
+    InstanceLeft {},
+    #[serde(rename = "session_report")]
+    SessionEnded {
+        report: SessionReportData,
+    },
+    WeeklyDigest {
+        digest: WeeklyDigestData,
+    },
+    AutomationChanged {
+        paused: Vec<String>,
+    },
+    BanPending {
+        ban: PendingBanData,
+    },
+    BanDiscarded {
+        ban: PendingBanData,
+        actor: String,
+    },
+    BanFailed {
+        ban: PendingBanData,
+        error: String,
+    },
+    GroupMemberJoined {
+        activity: GroupActivityData,
+    },
+    GroupMemberLeft {
+        activity: GroupActivityData,
+    },
+    GroupMemberRemoved {
+        activity: GroupActivityData,
+    },
+    GroupUserBanned {
+        activity: GroupActivityData,
+    },
+    GroupUserUnbanned {
+        activity: GroupActivityData,
+    },
+    GroupRoleAssigned {
+        activity: GroupActivityData,
+    },
+    GroupRoleUnassigned {
+        activity: GroupActivityData,
+    },
+    GroupPostCreated {
+        activity: GroupActivityData,
+    },
+    GroupInstanceCreated {
+        activity: GroupActivityData,
+    },
+    BlocklistAlert {
+        user: UserData,
+        reason: String,
+    },
+    BlocklistUpdated {
+        change: BlocklistChangeData,
+    },
+    BlocklistRolledBack {
+        version: BlocklistVersionData,
+    },
+}
+
+impl From<&AppEvent> for EventData {
+    fn from(event: &AppEvent) -> Self {
+        match event {
+            AppEvent::OnPlayerJoinedRaw(user_id) => Self::PlayerJoinedRaw {
+                user_id: user_id.clone(),
+            },
+            AppEvent::OnPlayerLeftRaw(user_id) => Self::PlayerLeftRaw {
+                user_id: user_id.clone(),
+            },
+            AppEvent::OnAvatarChangedRaw(user_id) => Self::AvatarChangedRaw {
+                user_id: user_id.clone(),
+            },
+            AppEvent::OnPlayerJoined(snapshot) => Self::PlayerJoined {
+                user: snapshot.into(),
+            },
+            AppEvent::OnPlayerLeft(snapshot) => Self::PlayerLeft {
+                user: snapshot.into(),
+            },
+            AppEvent::OnAvatarChanged(snapshot) => Self::AvatarChanged {
+                user: snapshot.into(),
+            },
+            AppEvent::OnAutoBanned(snapshot, reason) => Self::AutoBanned {
+                user: snapshot.into(),
+                reason: reason.clone(),
+            },
+            AppEvent::OnAutoInvited(snapshot) => Self::AutoInvited {
+                user: snapshot.into(),
+            },
+            AppEvent::OnInviteQuotaReached(limit) => Self::InviteQuotaReached {
+                limit: limit.clone(),
+            },
+            AppEvent::OnJoinRequestAccepted(snapshot) => Self::JoinRequestAccepted {
+                user: snapshot.into(),
+            },
+            AppEvent::OnJoinRequestRejected(snapshot, reason) => Self::JoinRequestRejected {
+                user: snapshot.into(),
+                reason: reason.clone(),
+            },
+            AppEvent::OnJoinRequestEscalated(snapshot, reason) => Self::JoinRequestEscalated {
+                user: snapshot.into(),
+                reason: reason.clone(),
+            },
+            AppEvent::OnRoleGranted(snapshot, role_id) => Self::RoleGranted {
+                user: snapshot.into(),
+                role_id: role_id.clone(),
+            },
+            AppEvent::OnRoleRevoked(snapshot, role_id) => Self::RoleRevoked {
+                user: snapshot.into(),
+                role_id: role_id.clone(),
+            },
+            AppEvent::OnWatchlistHit(snapshot) => Self::WatchlistHit {
+                user: snapshot.into(),
+            },
+            AppEvent::OnInstanceJoined(location) => Self::InstanceJoined {
+                location: location.clone(),
+            },
+            AppEvent::OnInstanceLeft => Self::InstanceLeft {},
+            AppEvent::OnSessionEnded(report) => Self::SessionEnded {
+                report: report.into(),
+            },
+            AppEvent::OnWeeklyDigest(digest) => Self::WeeklyDigest {
+                digest: digest.into(),
+            },
+            AppEvent::OnAutomationChanged(paused) => Self::AutomationChanged {
+                paused: paused.clone(),
+            },
+            AppEvent::OnBanPending(ban) => Self::BanPending { ban: ban.into() },
+            AppEvent::OnBanDiscarded(ban, actor) => Self::BanDiscarded {
+                ban: ban.into(),
+                actor: actor.clone(),
+            },
+            AppEvent::OnBanFailed(ban, error) => Self::BanFailed {
+                ban: ban.into(),
+                error: error.clone(),
+            },
+            AppEvent::OnGroupMemberJoined(activity) => Self::GroupMemberJoined {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupMemberLeft(activity) => Self::GroupMemberLeft {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupMemberRemoved(activity) => Self::GroupMemberRemoved {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupUserBanned(activity) => Self::GroupUserBanned {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupUserUnbanned(activity) => Self::GroupUserUnbanned {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupRoleAssigned(activity) => Self::GroupRoleAssigned {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupRoleUnassigned(activity) => Self::GroupRoleUnassigned {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupPostCreated(activity) => Self::GroupPostCreated {
+                activity: activity.into(),
+            },
+            AppEvent::OnGroupInstanceCreated(activity) => Self::GroupInstanceCreated {
+                activity: activity.into(),
+            },
+            AppEvent::OnBlocklistAlert(snapshot, reason) => Self::BlocklistAlert {
+                user: snapshot.into(),
+                reason: reason.clone(),
+            },
+            AppEvent::OnBlocklistUpdated(change) => Self::BlocklistUpdated {
+                change: change.into(),
+            },
+            AppEvent::OnBlocklistRolledBack(version) => Self::BlocklistRolledBack {
+                version: version.into(),
+            },
+        }
+    }
+}
+
+impl EventData {
+    /// The `event` tag this variant serializes with, the same name routing and templates use.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PlayerJoinedRaw { .. } => "player_joined_raw",
+            Self::PlayerLeftRaw { .. } => "player_left_raw",
+            Self::AvatarChangedRaw { .. } => "avatar_changed_raw",
+            Self::PlayerJoined { .. } => "player_joined",
+            Self::PlayerLeft { .. } => "player_left",
+            Self::AvatarChanged { .. } => "avatar_changed",
+            Self::AutoBanned { .. } => "auto_ban",
+            Self::AutoInvited { .. } => "auto_invite",
+            Self::InviteQuotaReached { .. } => "invite_quota",
+            Self::JoinRequestAccepted { .. } => "join_request_accepted",
+            Self::JoinRequestRejected { .. } => "join_request_rejected",
+            Self::JoinRequestEscalated { .. } => "join_request_escalated",
+            Self::RoleGranted { .. } => "role_granted",
+            Self::RoleRevoked { .. } => "role_revoked",
+            Self::WatchlistHit { .. } => "watchlist_hit",
+            Self::InstanceJoined { .. } => "instance_joined",
+            Self::InstanceLeft {} => "instance_left",
+            Self::SessionEnded { .. } => "session_report",
+            Self::WeeklyDigest { .. } => "weekly_digest",
+            Self::AutomationChanged { .. } => "automation_changed",
+            Self::BanPending { .. } => "ban_pending",
+            Self::BanDiscarded { .. } => "ban_discarded",
+            Self::BanFailed { .. } => "ban_failed",
+            Self::GroupMemberJoined { .. } => "group_member_joined",
+            Self::GroupMemberLeft { .. } => "group_member_left",
+            Self::GroupMemberRemoved { .. } => "group_member_removed",
+            Self::GroupUserBanned { .. } => "group_user_banned",
+            Self::GroupUserUnbanned { .. } => "group_user_unbanned",
+            Self::GroupRoleAssigned { .. } => "group_role_assigned",
+            Self::GroupRoleUnassigned { .. } => "group_role_unassigned",
+            Self::GroupPostCreated { .. } => "group_post_created",
+            Self::GroupInstanceCreated { .. } => "group_instance_created",
+            Self::BlocklistAlert { .. } => "blocklist_alert",
+            Self::BlocklistUpdated { .. } => "blocklist_updated",
+            Self::BlocklistRolledBack { .. } => "blocklist_rolled_back",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Envelope {
+    pub schema_version: u32,
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub data: EventData,
+}
+
+impl Envelope {
+    pub fn new(event: &AppEvent) -> Self {
+        let timestamp = Utc::now();
+        let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            id: format!(
+                "{:x}-{:x}",
+                timestamp.timestamp_nanos_opt().unwrap_or_default(),
+                sequence
+            ),
+            timestamp,
+            data: event.into(),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+//! Blocklist formats and signatures, shared by `vrc-manager` and `blocklist-server`.
+
+pub mod blocklist {
+    mod format;
+    pub mod signing;
+
+    pub use format::*;
+}
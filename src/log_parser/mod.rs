@@ -3,7 +3,7 @@ use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
@@ -50,6 +50,61 @@ static AVATAR_PATTERN: Lazy<Regex> = Lazy::new(|| {
     .unwrap()
 });
 
+static INSTANCE_JOIN_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?x)
+        ^(\d{4}\.\d{2}\.\d{2}\ \d{2}:\d{2}:\d{2})  # Timestamp
+        \s+\w+\s+-\s+                             # Log level and hyphen
+        \[Behaviour\]\sJoining\s                  # Event
+        (wrld_[^\s]+)                             # Location
+        ",
+    )
+    .unwrap()
+});
+
+static INSTANCE_LEFT_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?x)
+        ^(\d{4}\.\d{2}\.\d{2}\ \d{2}:\d{2}:\d{2})  # Timestamp
+        \s+\w+\s+-\s+                             # Log level and hyphen
+        \[Behaviour\]\sOnLeftRoom                  # Event
+        ",
+    )
+    .unwrap()
+});
+
+const RECENT_LINES_LIMIT: usize = 1000;
+
+static RECENT_LINES: Lazy<Mutex<VecDeque<String>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_LINES_LIMIT)));
+
+async fn remember_lines(data: &str) {
+    let mut recent = RECENT_LINES.lock().await;
+
+    for line in data.lines().filter(|line| !line.trim().is_empty()) {
+        if recent.len() == RECENT_LINES_LIMIT {
+            recent.pop_front();
+        }
+        recent.push_back(line.to_string());
+    }
+}
+
+/// The recent log lines around the last one mentioning any of `needles`.
+pub async fn excerpt(needles: &[&str], context: usize) -> Vec<String> {
+    let recent = RECENT_LINES.lock().await;
+
+    let Some(index) = recent
+        .iter()
+        .rposition(|line| needles.iter().any(|needle| line.contains(needle)))
+    else {
+        return Vec::new();
+    };
+
+    let start = index.saturating_sub(context);
+    let end = (index + context + 1).min(recent.len());
+    recent.range(start..end).cloned().collect()
+}
+
 pub async fn start_loop() -> Result<()> {
     let log_dir = get_vrchat_log_dir()?;
     let mut current_log_path = find_latest_log().await?;
@@ -79,42 +134,41 @@ pub async fn start_loop() -> Result<()> {
         match event {
             Some(event) => {
                 if event.paths.iter().any(|p| p == &log_dir) {
-                    if let EventKind::Create(_) = event.kind {
-                        if let Ok(newest_log) = find_latest_log().await {
-                            if newest_log != current_log_path {
-                                current_log_path = newest_log.clone();
-                                file = fs::File::open(&newest_log).await?;
-                                last_position = file.seek(std::io::SeekFrom::End(0)).await?;
-                                buffer.clear();
-                                watcher.unwatch(&current_log_path)?;
-                                watcher.watch(&newest_log, RecursiveMode::NonRecursive)?;
-                            }
-                        }
+                    if let EventKind::Create(_) = event.kind
+                        && let Ok(newest_log) = find_latest_log().await
+                        && newest_log != current_log_path
+                    {
+                        current_log_path = newest_log.clone();
+                        file = fs::File::open(&newest_log).await?;
+                        last_position = file.seek(std::io::SeekFrom::End(0)).await?;
+                        buffer.clear();
+                        watcher.unwatch(&current_log_path)?;
+                        watcher.watch(&newest_log, RecursiveMode::NonRecursive)?;
                     }
-                } else if event.paths.iter().any(|p| p == &current_log_path) {
-                    if let EventKind::Modify(_) = event.kind {
-                        let metadata = fs::metadata(&current_log_path).await?;
-                        let current_len = metadata.len();
-
-                        if current_len > last_position {
-                            file = reopen_log_file(&current_log_path).await?;
-                            file.seek(std::io::SeekFrom::Start(last_position)).await?;
-
-                            let mut chunk = Vec::new();
-                            file.take(current_len - last_position)
-                                .read_to_end(&mut chunk)
-                                .await?;
-
-                            process_log_chunk(
-                                String::from_utf8_lossy(&chunk).into_owned(),
-                                &mut buffer,
-                                program_start,
-                                &user_map,
-                            )
+                } else if event.paths.iter().any(|p| p == &current_log_path)
+                    && let EventKind::Modify(_) = event.kind
+                {
+                    let metadata = fs::metadata(&current_log_path).await?;
+                    let current_len = metadata.len();
+
+                    if current_len > last_position {
+                        file = reopen_log_file(&current_log_path).await?;
+                        file.seek(std::io::SeekFrom::Start(last_position)).await?;
+
+                        let mut chunk = Vec::new();
+                        file.take(current_len - last_position)
+                            .read_to_end(&mut chunk)
                             .await?;
 
-                            last_position = current_len;
-                        }
+                        process_log_chunk(
+                            String::from_utf8_lossy(&chunk).into_owned(),
+                            &mut buffer,
+                            program_start,
+                            &user_map,
+                        )
+                        .await?;
+
+                        last_position = current_len;
                     }
                 }
             }
@@ -139,59 +193,81 @@ async fn process_log_chunk(
 ) -> Result<()> {
     let data = std::mem::take(buffer) + &chunk;
 
+    // The trailing partial line is kept in `buffer` and remembered once it is complete.
+    if let Some(last_newline) = data.rfind('\n') {
+        remember_lines(&data[..last_newline]).await;
+    }
+
     for line in data.lines() {
         if let Some(captures) = JOIN_PATTERN.captures(line) {
             let timestamp_str = captures.get(1).unwrap().as_str();
             let username = captures.get(2).unwrap().as_str().trim();
             let user_id = captures.get(3).unwrap().as_str();
 
-            if let Ok(timestamp) = NaiveDateTime::parse_from_str(timestamp_str, "%Y.%m.%d %H:%M:%S")
+            if let Some(datetime) = parse_line_timestamp(timestamp_str)
+                && datetime > program_start
             {
-                let datetime = Local.from_local_datetime(&timestamp).unwrap();
-                if datetime > program_start {
-                    {
-                        let mut map = user_map.lock().await;
-                        map.insert(username.to_string(), user_id.to_string());
-                    }
-
-                    EVENT_BUS
-                        .publish(AppEvent::OnPlayerJoinedRaw(user_id.into()))
-                        .await;
+                {
+                    let mut map = user_map.lock().await;
+                    map.insert(username.to_string(), user_id.to_string());
                 }
+
+                EVENT_BUS
+                    .publish(AppEvent::OnPlayerJoinedRaw(user_id.into()))
+                    .await;
             }
         } else if let Some(captures) = LEAVE_PATTERN.captures(line) {
             let timestamp_str = captures.get(1).unwrap().as_str();
             let username = captures.get(2).unwrap().as_str().trim();
             let user_id = captures.get(3).unwrap().as_str();
 
-            if let Ok(timestamp) = NaiveDateTime::parse_from_str(timestamp_str, "%Y.%m.%d %H:%M:%S")
+            if let Some(datetime) = parse_line_timestamp(timestamp_str)
+                && datetime > program_start
             {
-                let datetime = Local.from_local_datetime(&timestamp).unwrap();
-                if datetime > program_start {
-                    let mut map = user_map.lock().await;
-                    map.remove(username);
+                let mut map = user_map.lock().await;
+                map.remove(username);
 
-                    EVENT_BUS
-                        .publish(AppEvent::OnPlayerLeftRaw(user_id.into()))
-                        .await;
-                }
+                EVENT_BUS
+                    .publish(AppEvent::OnPlayerLeftRaw(user_id.into()))
+                    .await;
             }
         } else if let Some(captures) = AVATAR_PATTERN.captures(line) {
             let timestamp_str = captures.get(1).unwrap().as_str();
             let username = captures.get(2).unwrap().as_str().trim();
 
-            if let Ok(timestamp) = NaiveDateTime::parse_from_str(timestamp_str, "%Y.%m.%d %H:%M:%S")
+            if let Some(datetime) = parse_line_timestamp(timestamp_str)
+                && datetime > program_start
             {
-                let datetime = Local.from_local_datetime(&timestamp).unwrap();
-                if datetime > program_start {
-                    let map = user_map.lock().await;
-                    if let Some(user_id) = map.get(username) {
-                        EVENT_BUS
-                            .publish(AppEvent::OnAvatarChangedRaw(user_id.into()))
-                            .await;
-                    }
+                let map = user_map.lock().await;
+                if let Some(user_id) = map.get(username) {
+                    EVENT_BUS
+                        .publish(AppEvent::OnAvatarChangedRaw(user_id.into()))
+                        .await;
                 }
             }
+        } else if let Some(captures) = INSTANCE_JOIN_PATTERN.captures(line) {
+            let timestamp_str = captures.get(1).unwrap().as_str();
+            let location = captures.get(2).unwrap().as_str();
+
+            if let Some(datetime) = parse_line_timestamp(timestamp_str)
+                && datetime > program_start
+            {
+                user_map.lock().await.clear();
+
+                EVENT_BUS
+                    .publish(AppEvent::OnInstanceJoined(location.into()))
+                    .await;
+            }
+        } else if let Some(captures) = INSTANCE_LEFT_PATTERN.captures(line) {
+            let timestamp_str = captures.get(1).unwrap().as_str();
+
+            if let Some(datetime) = parse_line_timestamp(timestamp_str)
+                && datetime > program_start
+            {
+                user_map.lock().await.clear();
+
+                EVENT_BUS.publish(AppEvent::OnInstanceLeft).await;
+            }
         }
     }
 
@@ -219,6 +295,13 @@ fn get_vrchat_log_dir() -> Result<PathBuf> {
         .join("VRChat"))
 }
 
+/// A log line's local timestamp. Times a DST change skips don't exist, so those lines are
+/// dropped rather than taking the watcher down; repeated ones resolve to the earlier time.
+fn parse_line_timestamp(timestamp: &str) -> Option<DateTime<Local>> {
+    let timestamp = NaiveDateTime::parse_from_str(timestamp, "%Y.%m.%d %H:%M:%S").ok()?;
+    Local.from_local_datetime(&timestamp).earliest()
+}
+
 pub async fn find_latest_log() -> Result<PathBuf> {
     let log_dir = get_vrchat_log_dir()?;
     let mut latest_time = None;
@@ -228,17 +311,15 @@ pub async fn find_latest_log() -> Result<PathBuf> {
 
     while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
-        if path.is_file() {
-            if let Some(filename) = path.file_name().and_then(|s| s.to_str()) {
-                if filename.starts_with("output_log_") && filename.ends_with(".txt") {
-                    if let Some(datetime) = parse_log_timestamp(filename) {
-                        if latest_time.map_or(true, |t: DateTime<Local>| datetime > t) {
-                            latest_time = Some(datetime);
-                            latest_path = Some(path);
-                        }
-                    }
-                }
-            }
+        if path.is_file()
+            && let Some(filename) = path.file_name().and_then(|s| s.to_str())
+            && filename.starts_with("output_log_")
+            && filename.ends_with(".txt")
+            && let Some(datetime) = parse_log_timestamp(filename)
+            && latest_time.is_none_or(|t: DateTime<Local>| datetime > t)
+        {
+            latest_time = Some(datetime);
+            latest_path = Some(path);
         }
     }
 
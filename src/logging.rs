@@ -6,7 +6,7 @@ use tracing_subscriber::FmtSubscriber;
 use tracing_subscriber::fmt::writer::MakeWriterExt;
 
 use std::fs::OpenOptions;
-use std::io::{self, BufRead, BufReader, Seek, Write};
+use std::io::{self, Read, Seek, Write};
 
 const MAX_LOG_LINES: usize = 250;
 
@@ -15,10 +15,13 @@ fn prepare_log_file(path: &Path) -> io::Result<File> {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open(path)?;
 
-    let reader = BufReader::new(&file);
-    let lines: Vec<String> = reader.lines().filter_map(|line| line.ok()).collect();
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    let contents = String::from_utf8_lossy(&contents);
+    let lines: Vec<&str> = contents.lines().collect();
 
     if lines.len() > MAX_LOG_LINES {
         let last_lines = lines
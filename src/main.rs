@@ -3,17 +3,42 @@ use tracing::error;
 
 use crate::config::CONFIG;
 
+mod api;
+mod blocklist;
 mod config;
+mod console;
 mod discord_webhook;
+mod event_webhook;
 mod events;
 mod log_parser;
 mod logging;
+mod mqtt;
+mod notifiers;
+mod session_report;
+mod storage;
 mod vrchat;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().is_some_and(|command| command == "blocklist") {
+        return blocklist::cli::run(&args[1..]).await;
+    }
+
     logging::init();
 
+    vrchat::presence::init();
+    vrchat::encounters::init();
+
+    if CONFIG.session_reports {
+        session_report::init();
+    }
+
+    // Started before the log parser so the retained instance and roster topics see every event.
+    if CONFIG.mqtt.enabled {
+        mqtt::init();
+    }
+
     tokio::spawn(async move {
         if let Err(err) = log_parser::start_loop().await {
             error!("Log parser failed: {:#}", err);
@@ -21,8 +46,12 @@ async fn main() -> Result<()> {
     });
 
     let auth_config = vrchat::auth().await?;
+    if let Err(e) = vrchat::presence::learn_local_user(&auth_config).await {
+        error!("Failed to look up the logged in user: {:#}", e);
+    }
 
     vrchat::event_enricher::init(&auth_config);
+    vrchat::watchlist::init();
 
     if CONFIG.auto_ban.enabled {
         vrchat::auto_ban(&auth_config);
@@ -32,14 +61,46 @@ async fn main() -> Result<()> {
         vrchat::auto_invite(&auth_config);
     }
 
+    if CONFIG.auto_roles.enabled {
+        vrchat::auto_roles(&auth_config);
+    }
+
+    if CONFIG.join_requests.enabled {
+        vrchat::join_requests(&auth_config);
+    }
+
+    blocklist::remote::init();
+    blocklist::history::init();
+
+    if CONFIG.pardons.enabled {
+        vrchat::pardons::init();
+    }
+
+    // Pardons learn about unbans in the VRChat UI from the group audit log.
+    if CONFIG.group_audit.enabled || CONFIG.pardons.enabled {
+        vrchat::group_audit::init(&auth_config);
+    }
+
     if CONFIG.discord_webhook.enabled {
         discord_webhook::init(&auth_config);
     }
 
+    notifiers::init();
+
+    if CONFIG.event_webhook.enabled {
+        event_webhook::init();
+    }
+
     if CONFIG.log_avatar_id {
         vrchat::log_avatar_id();
     }
 
+    if CONFIG.api.enabled {
+        api::init(&auth_config);
+    }
+
+    console::init(&auth_config);
+
     loop {
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
     }
@@ -0,0 +1,198 @@
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::config::CONFIG;
+use crate::events::schema::Envelope;
+use crate::events::{AppEvent, EVENT_BUS};
+use crate::vrchat::automation;
+
+const CHANNEL_CAPACITY: usize = 100;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone)]
+struct RosterEntry {
+    id: String,
+    display_name: String,
+}
+
+fn prefix() -> String {
+    match &CONFIG.mqtt.topic_prefix {
+        Some(prefix) => prefix.trim_end_matches('/').to_string(),
+        None => format!(
+            "vrc-manager/{}",
+            CONFIG.group_id.as_deref().unwrap_or("default")
+        ),
+    }
+}
+
+fn event_topic(prefix: &str, event: &str) -> String {
+    let suffix = match CONFIG.mqtt.topics.get(event) {
+        Some(topic) => topic.as_str(),
+        None => match event {
+            "player_joined" => "joined",
+            "player_left" => "left",
+            event => event,
+        },
+    };
+
+    format!("{prefix}/{suffix}")
+}
+
+/// Never waits on the broker, so a dead connection can't stall the event bus.
+fn publish(client: &AsyncClient, topic: String, retain: bool, payload: String) {
+    if let Err(e) = client.try_publish(&topic, QoS::AtLeastOnce, retain, payload) {
+        warn!("Dropping MQTT message for {}: {}", topic, e);
+    }
+}
+
+async fn handle_command(topic: &str, payload: &[u8], prefix: &str) {
+    let Some(command) = topic.strip_prefix(&format!("{prefix}/command/")) else {
+        return;
+    };
+
+    let name = String::from_utf8_lossy(payload);
+    let name = match name.trim() {
+        "" => automation::ALL,
+        name => name,
+    };
+
+    let result = match command {
+        "pause" => automation::pause(name).await,
+        "resume" => automation::resume(name).await,
+        _ => {
+            warn!("Unknown MQTT command: {}", command);
+            return;
+        }
+    };
+
+    match result {
+        Ok(()) => info!("MQTT command: {} {}", command, name),
+        Err(e) => warn!("MQTT command {} failed: {:#}", command, e),
+    }
+}
+
+async fn run_eventloop(mut eventloop: EventLoop, client: AsyncClient, prefix: String) {
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("Connected to MQTT broker");
+                publish(&client, format!("{prefix}/status"), true, "online".into());
+
+                // Subscriptions don't survive a clean session, so renew them on every connect.
+                if CONFIG.mqtt.commands
+                    && let Err(e) =
+                        client.try_subscribe(format!("{prefix}/command/#"), QoS::AtLeastOnce)
+                {
+                    error!("Failed to subscribe to MQTT command topics: {}", e);
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(message))) => {
+                handle_command(&message.topic, &message.payload, &prefix).await;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                warn!(
+                    "MQTT connection failed, retrying in {}s: {}",
+                    RECONNECT_DELAY.as_secs(),
+                    e
+                );
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn publish_events(client: AsyncClient, prefix: String) {
+    let mut rx = EVENT_BUS.subscribe().await;
+    let mut location: Option<String> = None;
+    let mut roster: HashMap<String, RosterEntry> = HashMap::new();
+
+    while let Some(event) = rx.recv().await {
+        let envelope = Envelope::new(&event);
+
+        match serde_json::to_string(&envelope) {
+            Ok(payload) => publish(
+                &client,
+                event_topic(&prefix, envelope.data.name()),
+                false,
+                payload,
+            ),
+            Err(e) => error!("Failed to serialize {} event: {}", envelope.data.name(), e),
+        }
+
+        // Keep the retained state topics in sync, so late subscribers see the current picture.
+        match event {
+            AppEvent::OnInstanceJoined(new_location) => {
+                location = Some(new_location);
+                roster.clear();
+            }
+            AppEvent::OnInstanceLeft => {
+                location = None;
+                roster.clear();
+            }
+            AppEvent::OnPlayerJoined(snapshot) => {
+                roster.insert(
+                    snapshot.id().to_string(),
+                    RosterEntry {
+                        id: snapshot.id().to_string(),
+                        display_name: snapshot.user.display_name,
+                    },
+                );
+            }
+            AppEvent::OnPlayerLeft(snapshot) => {
+                roster.remove(snapshot.id());
+            }
+            AppEvent::OnAutomationChanged(paused) => {
+                let payload = json!({ "paused": paused }).to_string();
+                publish(&client, format!("{prefix}/automation"), true, payload);
+                continue;
+            }
+            _ => continue,
+        }
+
+        let mut players = roster.values().cloned().collect::<Vec<_>>();
+        players.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+
+        let instance = json!({ "location": location }).to_string();
+        let roster_payload = json!({
+            "location": location,
+            "count": players.len(),
+            "players": players,
+        })
+        .to_string();
+
+        publish(&client, format!("{prefix}/instance"), true, instance);
+        publish(&client, format!("{prefix}/roster"), true, roster_payload);
+    }
+}
+
+pub fn init() {
+    let settings = &CONFIG.mqtt;
+    let prefix = prefix();
+
+    let mut options = MqttOptions::new(
+        settings.client_id.as_deref().unwrap_or("vrc-manager"),
+        settings.host.as_deref().unwrap_or("localhost"),
+        settings.port.unwrap_or(1883),
+    );
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        format!("{prefix}/status"),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+
+    if let Some(username) = &settings.username {
+        options.set_credentials(username, settings.password.as_deref().unwrap_or_default());
+    }
+
+    let (client, eventloop) = AsyncClient::new(options, CHANNEL_CAPACITY);
+
+    tokio::spawn(run_eventloop(eventloop, client.clone(), prefix.clone()));
+    tokio::spawn(publish_events(client, prefix));
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::routing::Filter;
+use super::{Notification, send_request};
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct MatrixNotifier {
+    pub homeserver: String,
+    pub access_token: String,
+    pub room_id: String,
+    #[serde(flatten)]
+    pub filter: Filter,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub async fn send(
+    client: &Client,
+    config: &MatrixNotifier,
+    notification: &Notification,
+) -> Result<()> {
+    // The transaction ID stays the same across retries, so the homeserver drops duplicates.
+    // It's scoped to the access token, so the room keeps rooms sharing a token apart.
+    let transaction_id = format!("vrc-manager-{}-{}", notification.id, config.room_id);
+    let url = format!(
+        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+        config.homeserver.trim_end_matches('/'),
+        urlencoding::encode(&config.room_id),
+        urlencoding::encode(&transaction_id)
+    );
+
+    let content = json!({
+        "msgtype": "m.text",
+        "body": format!("{}\n{}", notification.title(), notification.body()),
+        "format": "org.matrix.custom.html",
+        "formatted_body": format!(
+            "<b>{}</b><br>{}",
+            escape_html(notification.title()),
+            escape_html(&notification.body())
+        ),
+    });
+
+    send_request(
+        client
+            .put(url)
+            .bearer_auth(&config.access_token)
+            .json(&content),
+    )
+    .await
+}
@@ -0,0 +1,648 @@
+use chrono::{Duration, Local};
+
+use crate::blocklist::BlocklistChange;
+use crate::blocklist::history::BlocklistVersion;
+use crate::discord_webhook::interactions::Action;
+use crate::discord_webhook::template::Vars;
+use crate::events::{AppEvent, UserSnapshot};
+use crate::session_report::{SessionReport, WeeklyDigest};
+use crate::vrchat::ban_approval::PendingBan;
+use crate::vrchat::group_audit::GroupActivity;
+
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub value: String,
+    pub inline: bool,
+}
+
+impl Field {
+    fn new(name: &str, value: String) -> Self {
+        Self {
+            name: name.into(),
+            value,
+            inline: false,
+        }
+    }
+
+    fn inline(name: &str, value: String) -> Self {
+        Self {
+            inline: true,
+            ..Self::new(name, value)
+        }
+    }
+}
+
+/// What an event says, before any backend renders it. Discord shows it as an embed,
+/// the other backends as a title and a text body.
+#[derive(Clone, Debug)]
+pub struct Message {
+    /// The routing and template name, e.g. `auto_ban`.
+    pub event: &'static str,
+    pub title: String,
+    pub description: String,
+    pub fields: Vec<Field>,
+    pub thumbnail: Option<String>,
+    pub footer: Option<String>,
+    pub color: u32,
+    /// Buttons, only Discord can show them.
+    pub actions: Vec<Action>,
+    /// Placeholders for templates.
+    pub vars: Vars,
+}
+
+impl Message {
+    fn new(event: &'static str, title: String, description: String, color: u32) -> Self {
+        Self {
+            event,
+            title,
+            description,
+            fields: Vec::new(),
+            thumbnail: None,
+            footer: None,
+            color,
+            actions: Vec::new(),
+            vars: Vars::new(),
+        }
+    }
+
+    fn about(mut self, snapshot: &UserSnapshot) -> Self {
+        self.thumbnail = Some(snapshot.user.current_avatar_thumbnail_image_url.clone());
+        self.vars.extend(user_vars(snapshot));
+        self
+    }
+
+    fn field(mut self, field: Field) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    fn var(mut self, name: &'static str, value: String) -> Self {
+        self.vars.insert(name, value);
+        self
+    }
+
+    fn actions(mut self, actions: Vec<Action>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    /// The description followed by the fields, for backends without embeds.
+    pub fn text(&self) -> String {
+        let mut text = self.description.clone();
+
+        for field in &self.fields {
+            text.push_str(&format!("\n{}: {}", field.name, field.value));
+        }
+
+        text
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {:02}m", hours, minutes % 60),
+    }
+}
+
+/// Field values are capped at 1024 characters, so long changes are cut short.
+fn id_list(ids: &[String]) -> String {
+    match ids.len() {
+        0 => "None".to_string(),
+        n if n > 20 => format!("{}\n...and {} more", ids[..20].join("\n"), n - 20),
+        _ => ids.join("\n"),
+    }
+}
+
+fn user_vars(snapshot: &UserSnapshot) -> Vars {
+    let user = &snapshot.user;
+
+    Vars::from([
+        ("display_name", user.display_name.clone()),
+        ("user_id", user.id.clone()),
+        (
+            "avatar_file_id",
+            snapshot.avatar_file_id.clone().unwrap_or_default(),
+        ),
+        (
+            "avatar_thumbnail_url",
+            user.current_avatar_thumbnail_image_url.clone(),
+        ),
+        (
+            "timestamp",
+            snapshot
+                .captured_at
+                .with_timezone(&Local)
+                .format(TIMESTAMP_FORMAT)
+                .to_string(),
+        ),
+    ])
+}
+
+/// Buttons for acting on a user who is in the instance. Only offered on alerts, an embed
+/// with buttons is always sent on its own, so join and avatar logs would no longer batch.
+fn moderation_actions(snapshot: &UserSnapshot) -> Vec<Action> {
+    let mut actions = vec![Action::Ban(snapshot.id().to_string())];
+
+    if let Some(avatar_id) = &snapshot.avatar_file_id {
+        actions.push(Action::BlockAvatar(avatar_id.clone()));
+        actions.push(Action::BlockAuthor(avatar_id.clone()));
+    }
+
+    actions.push(Action::Watchlist(snapshot.id().to_string()));
+    actions
+}
+
+fn user_id_field(snapshot: &UserSnapshot) -> Field {
+    Field::new("User ID", snapshot.id().to_string())
+}
+
+fn avatar_field(snapshot: &UserSnapshot) -> Field {
+    Field::new(
+        "Avatar File ID",
+        snapshot.avatar_file_id.clone().unwrap_or_default(),
+    )
+}
+
+fn auto_ban(snapshot: &UserSnapshot, reason: &str) -> Message {
+    Message::new(
+        "auto_ban",
+        "User Banned".into(),
+        format!("{} has been banned", snapshot.user.display_name),
+        0xFF0000,
+    )
+    .about(snapshot)
+    .field(user_id_field(snapshot))
+    .field(Field::new("Reason", reason.to_string()))
+    .var("reason", reason.to_string())
+    .actions(vec![Action::Unban(snapshot.id().to_string())])
+}
+
+fn auto_invite(snapshot: &UserSnapshot) -> Message {
+    Message::new(
+        "auto_invite",
+        "User Invited".into(),
+        format!(
+            "{} has been automatically invited",
+            snapshot.user.display_name
+        ),
+        0x0000FF,
+    )
+    .about(snapshot)
+    .field(user_id_field(snapshot))
+}
+
+fn invite_quota(limit: &str) -> Message {
+    Message::new(
+        "invite_quota",
+        "Invite Quota Reached".into(),
+        format!(
+            "The {} invite quota has been reached, further invites are held back",
+            limit
+        ),
+        0x808080,
+    )
+    .var("reason", format!("{} invite quota reached", limit))
+}
+
+fn join_request(
+    snapshot: &UserSnapshot,
+    event: &'static str,
+    title: &str,
+    reason: Option<&str>,
+    color: u32,
+) -> Message {
+    let message = Message::new(
+        event,
+        title.into(),
+        format!("Group join request from {}", snapshot.user.display_name),
+        color,
+    )
+    .about(snapshot)
+    .field(user_id_field(snapshot))
+    .var("reason", reason.unwrap_or_default().to_string());
+
+    match reason {
+        Some(reason) => message.field(Field::new("Reason", reason.to_string())),
+        None => message,
+    }
+}
+
+fn role_change(snapshot: &UserSnapshot, role_id: &str, granted: bool) -> Message {
+    let (event, title, verb, color) = if granted {
+        ("role_granted", "Role Granted", "granted", 0x00FFFF)
+    } else {
+        ("role_revoked", "Role Revoked", "revoked", 0xFF00FF)
+    };
+
+    Message::new(
+        event,
+        title.into(),
+        format!(
+            "Role {} has been automatically {} for {}",
+            role_id, verb, snapshot.user.display_name
+        ),
+        color,
+    )
+    .about(snapshot)
+    .field(user_id_field(snapshot))
+    .var("role_id", role_id.to_string())
+}
+
+fn watchlist_hit(snapshot: &UserSnapshot) -> Message {
+    Message::new(
+        "watchlist_hit",
+        "Watchlisted User Joined".into(),
+        format!("{} is on the watchlist", snapshot.user.display_name),
+        0x800080,
+    )
+    .about(snapshot)
+    .field(user_id_field(snapshot))
+    .actions(moderation_actions(snapshot))
+}
+
+fn ban_pending(ban: &PendingBan) -> Message {
+    Message::new(
+        "ban_pending",
+        format!("Ban #{} Needs Approval", ban.id),
+        format!(
+            "{} matched the blocklist and will be banned once approved",
+            ban.snapshot.user.display_name
+        ),
+        0xFFA500,
+    )
+    .about(&ban.snapshot)
+    .field(user_id_field(&ban.snapshot))
+    .field(Field::new("Reason", ban.reason.clone()))
+    .field(Field::new(
+        "Expires",
+        ban.expires_at
+            .with_timezone(&Local)
+            .format(TIMESTAMP_FORMAT)
+            .to_string(),
+    ))
+    .var("reason", ban.reason.clone())
+    .var("ban_id", ban.id.to_string())
+    .actions(vec![Action::ApproveBan(ban.id), Action::DiscardBan(ban.id)])
+}
+
+fn ban_discarded(ban: &PendingBan, actor: &str) -> Message {
+    let mut message = Message::new(
+        "ban_discarded",
+        format!("Ban #{} Discarded", ban.id),
+        format!(
+            "The pending ban of {} was discarded by {}",
+            ban.snapshot.user.display_name, actor
+        ),
+        0x808080,
+    )
+    .about(&ban.snapshot)
+    .var("reason", ban.reason.clone())
+    .var("ban_id", ban.id.to_string());
+
+    message.thumbnail = None;
+    message
+}
+
+fn ban_failed(ban: &PendingBan, error: &str) -> Message {
+    Message::new(
+        "ban_failed",
+        format!("Ban #{} Failed", ban.id),
+        format!(
+            "The timed out ban of {} was approved but couldn't be carried out",
+            ban.snapshot.user.display_name
+        ),
+        0xFF4500,
+    )
+    .about(&ban.snapshot)
+    .field(user_id_field(&ban.snapshot))
+    .field(Field::new("Error", error.to_string()))
+    .var("reason", ban.reason.clone())
+    .var("ban_id", ban.id.to_string())
+    .var("error", error.to_string())
+}
+
+fn blocklist_alert(snapshot: &UserSnapshot, reason: &str) -> Message {
+    Message::new(
+        "blocklist_alert",
+        "Listed Avatar Seen".into(),
+        format!("{} is wearing a listed avatar", snapshot.user.display_name),
+        0xFFA500,
+    )
+    .about(snapshot)
+    .field(user_id_field(snapshot))
+    .field(Field::new("Reason", reason.to_string()))
+    .var("reason", reason.to_string())
+    .actions(moderation_actions(snapshot))
+}
+
+fn blocklist_updated(change: &BlocklistChange) -> Message {
+    Message::new(
+        "blocklist_updated",
+        format!("Blocklist {} Updated", change.source),
+        format!(
+            "{} added, {} removed",
+            change.added.len(),
+            change.removed.len()
+        ),
+        0x808080,
+    )
+    .field(Field::new("Added", id_list(&change.added)))
+    .field(Field::new("Removed", id_list(&change.removed)))
+    .var("source", change.source.clone())
+    .var("added", change.added.len().to_string())
+    .var("removed", change.removed.len().to_string())
+}
+
+fn blocklist_rolled_back(version: &BlocklistVersion) -> Message {
+    let restored = version.rollback_to.unwrap_or_default();
+
+    Message::new(
+        "blocklist_rolled_back",
+        "Blocklist Rolled Back".into(),
+        format!(
+            "{} restored version {} of the avatar blocklist: {}",
+            version.author, restored, version.reason
+        ),
+        0xFFA500,
+    )
+    .field(Field::new("Added", id_list(&version.added)))
+    .field(Field::new("Removed", id_list(&version.removed)))
+    .var("actor", version.author.clone())
+    .var("version", restored.to_string())
+    .var("added", version.added.len().to_string())
+    .var("removed", version.removed.len().to_string())
+    .var("reason", version.reason.clone())
+}
+
+fn group_activity(activity: &GroupActivity) -> Message {
+    let target = activity.target_id.clone().unwrap_or_default();
+    let color = match activity.event_name() {
+        "group_user_banned" | "group_member_removed" => 0xFF0000,
+        "group_user_unbanned" | "group_member_joined" => 0x00FF00,
+        _ => 0x808080,
+    };
+
+    let mut message = Message::new(
+        activity.event_name(),
+        activity.title().into(),
+        activity.summary(),
+        color,
+    )
+    .field(Field::inline("By", activity.actor().to_string()))
+    .var("actor", activity.actor().to_string())
+    .var("target_id", target.clone())
+    .var("description", activity.summary())
+    .var(
+        "timestamp",
+        activity
+            .created_at
+            .with_timezone(&Local)
+            .format(TIMESTAMP_FORMAT)
+            .to_string(),
+    );
+
+    if !target.is_empty() {
+        message = message.field(Field::inline("Target", format!("`{target}`")));
+    }
+
+    message
+}
+
+fn session_report(report: &SessionReport) -> Message {
+    let duration = format_duration(report.duration());
+    let watchlist_hits = if report.watchlist_hits.is_empty() {
+        "None".to_string()
+    } else {
+        report.watchlist_hits.join(", ")
+    };
+
+    Message::new(
+        "session_report",
+        "Session Summary".into(),
+        format!("Left {} after {}", report.location, duration),
+        0x808080,
+    )
+    .field(Field::inline(
+        "Players",
+        format!(
+            "{} peak, {:.1} average",
+            report.peak_players, report.average_players
+        ),
+    ))
+    .field(Field::inline(
+        "Unique Players",
+        format!(
+            "{} ({} new, {} returning)",
+            report.unique_players, report.new_players, report.returning_players
+        ),
+    ))
+    .field(Field::inline(
+        "Avatar Switches",
+        report.avatar_switches.to_string(),
+    ))
+    .field(Field::inline("Bans", report.bans.to_string()))
+    .field(Field::inline("Invites", report.invites.to_string()))
+    .field(Field::inline("Watchlist Hits", watchlist_hits))
+    .var("duration", duration)
+    .var("peak_players", report.peak_players.to_string())
+    .var("unique_players", report.unique_players.to_string())
+}
+
+fn weekly_digest(digest: &WeeklyDigest) -> Message {
+    let duration = format_duration(digest.time_in_instances);
+    let watchlist_hits = if digest.watchlist_hits.is_empty() {
+        "None".to_string()
+    } else {
+        digest.watchlist_hits.join(", ")
+    };
+
+    Message::new(
+        "weekly_digest",
+        "Weekly Digest".into(),
+        format!(
+            "{} sessions in {} instances over the last week, {} in total",
+            digest.sessions, digest.locations, duration
+        ),
+        0x4682B4,
+    )
+    .field(Field::inline(
+        "Players",
+        format!(
+            "{} met ({} new), {} peak",
+            digest.players_met, digest.new_players, digest.peak_players
+        ),
+    ))
+    .field(Field::inline(
+        "Avatar Switches",
+        digest.avatar_switches.to_string(),
+    ))
+    .field(Field::inline("Bans", digest.bans.to_string()))
+    .field(Field::inline("Invites", digest.invites.to_string()))
+    .field(Field::inline("Watchlist Hits", watchlist_hits))
+    .var("duration", duration)
+    .var("sessions", digest.sessions.to_string())
+    .var("new_players", digest.new_players.to_string())
+}
+
+fn presence(
+    snapshot: &UserSnapshot,
+    event: &'static str,
+    title: &str,
+    action: &str,
+    color: u32,
+) -> Message {
+    Message::new(
+        event,
+        title.into(),
+        format!("{} {}", snapshot.user.display_name, action),
+        color,
+    )
+    .about(snapshot)
+    .field(user_id_field(snapshot))
+    .field(avatar_field(snapshot))
+}
+
+/// The routing name of an event, or `None` for events nobody is notified about.
+/// Cheap, so routing can be decided before the message is built.
+pub fn event_name(event: &AppEvent) -> Option<&'static str> {
+    let name = match event {
+        AppEvent::OnAutoBanned(..) => "auto_ban",
+        AppEvent::OnAutoInvited(_) => "auto_invite",
+        AppEvent::OnInviteQuotaReached(_) => "invite_quota",
+        AppEvent::OnJoinRequestAccepted(_) => "join_request_accepted",
+        AppEvent::OnJoinRequestRejected(..) => "join_request_rejected",
+        AppEvent::OnJoinRequestEscalated(..) => "join_request_escalated",
+        AppEvent::OnRoleGranted(..) => "role_granted",
+        AppEvent::OnRoleRevoked(..) => "role_revoked",
+        AppEvent::OnWatchlistHit(_) => "watchlist_hit",
+        AppEvent::OnBanPending(_) => "ban_pending",
+        AppEvent::OnBanDiscarded(..) => "ban_discarded",
+        AppEvent::OnBanFailed(..) => "ban_failed",
+        AppEvent::OnBlocklistAlert(..) => "blocklist_alert",
+        AppEvent::OnBlocklistUpdated(_) => "blocklist_updated",
+        AppEvent::OnBlocklistRolledBack(_) => "blocklist_rolled_back",
+        AppEvent::OnGroupMemberJoined(activity)
+        | AppEvent::OnGroupMemberLeft(activity)
+        | AppEvent::OnGroupMemberRemoved(activity)
+        | AppEvent::OnGroupUserBanned(activity)
+        | AppEvent::OnGroupUserUnbanned(activity)
+        | AppEvent::OnGroupRoleAssigned(activity)
+        | AppEvent::OnGroupRoleUnassigned(activity)
+        | AppEvent::OnGroupPostCreated(activity)
+        | AppEvent::OnGroupInstanceCreated(activity) => activity.event_name(),
+        AppEvent::OnSessionEnded(_) => "session_report",
+        AppEvent::OnWeeklyDigest(_) => "weekly_digest",
+        AppEvent::OnPlayerJoined(_) => "player_joined",
+        AppEvent::OnAvatarChanged(_) => "avatar_changed",
+        AppEvent::OnPlayerLeft(_) => "player_left",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// The instance an event is about when it isn't the current one, empty for none at all.
+pub fn instance(event: &AppEvent) -> Option<String> {
+    match event {
+        AppEvent::OnSessionEnded(report) => Some(report.location.clone()),
+        AppEvent::OnBlocklistUpdated(_)
+        | AppEvent::OnBlocklistRolledBack(_)
+        | AppEvent::OnWeeklyDigest(_) => Some(String::new()),
+        // Group activity happens outside our instance, except for the instances it opens.
+        AppEvent::OnGroupInstanceCreated(activity) => {
+            Some(activity.target_id.clone().unwrap_or_default())
+        }
+        AppEvent::OnGroupMemberJoined(_)
+        | AppEvent::OnGroupMemberLeft(_)
+        | AppEvent::OnGroupMemberRemoved(_)
+        | AppEvent::OnGroupUserBanned(_)
+        | AppEvent::OnGroupUserUnbanned(_)
+        | AppEvent::OnGroupRoleAssigned(_)
+        | AppEvent::OnGroupRoleUnassigned(_)
+        | AppEvent::OnGroupPostCreated(_) => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// Builds the message for an event, or `None` for events nobody is notified about.
+pub fn describe(event: &AppEvent) -> Option<Message> {
+    let message = match event {
+        AppEvent::OnAutoBanned(snapshot, reason) => auto_ban(snapshot, reason),
+        AppEvent::OnAutoInvited(snapshot) => auto_invite(snapshot),
+        AppEvent::OnInviteQuotaReached(limit) => invite_quota(limit),
+        AppEvent::OnJoinRequestAccepted(snapshot) => join_request(
+            snapshot,
+            "join_request_accepted",
+            "Join Request Accepted",
+            None,
+            0x00FF00,
+        ),
+        AppEvent::OnJoinRequestRejected(snapshot, reason) => join_request(
+            snapshot,
+            "join_request_rejected",
+            "Join Request Rejected",
+            Some(reason),
+            0xFF0000,
+        ),
+        AppEvent::OnJoinRequestEscalated(snapshot, reason) => join_request(
+            snapshot,
+            "join_request_escalated",
+            "Join Request Needs Review",
+            Some(reason),
+            0xFFFF00,
+        ),
+        AppEvent::OnRoleGranted(snapshot, role_id) => role_change(snapshot, role_id, true),
+        AppEvent::OnRoleRevoked(snapshot, role_id) => role_change(snapshot, role_id, false),
+        AppEvent::OnWatchlistHit(snapshot) => watchlist_hit(snapshot),
+        AppEvent::OnBanPending(ban) => ban_pending(ban),
+        AppEvent::OnBanDiscarded(ban, actor) => ban_discarded(ban, actor),
+        AppEvent::OnBanFailed(ban, error) => ban_failed(ban, error),
+        AppEvent::OnBlocklistAlert(snapshot, reason) => blocklist_alert(snapshot, reason),
+        AppEvent::OnBlocklistUpdated(change) => blocklist_updated(change),
+        AppEvent::OnBlocklistRolledBack(version) => blocklist_rolled_back(version),
+        AppEvent::OnGroupMemberJoined(activity)
+        | AppEvent::OnGroupMemberLeft(activity)
+        | AppEvent::OnGroupMemberRemoved(activity)
+        | AppEvent::OnGroupUserBanned(activity)
+        | AppEvent::OnGroupUserUnbanned(activity)
+        | AppEvent::OnGroupRoleAssigned(activity)
+        | AppEvent::OnGroupRoleUnassigned(activity)
+        | AppEvent::OnGroupPostCreated(activity)
+        | AppEvent::OnGroupInstanceCreated(activity) => group_activity(activity),
+        AppEvent::OnSessionEnded(report) => session_report(report),
+        AppEvent::OnWeeklyDigest(digest) => weekly_digest(digest),
+        AppEvent::OnPlayerJoined(snapshot) => presence(
+            snapshot,
+            "player_joined",
+            "Player Joined",
+            "has joined the instance!",
+            0x00FF00,
+        ),
+        AppEvent::OnAvatarChanged(snapshot) => {
+            snapshot.avatar_file_id.as_ref()?;
+            presence(
+                snapshot,
+                "avatar_changed",
+                "Avatar Changed",
+                "has changed their avatar!",
+                0xFFFF00,
+            )
+        }
+        AppEvent::OnPlayerLeft(snapshot) => presence(
+            snapshot,
+            "player_left",
+            "Player Left",
+            "has left the instance",
+            0xFFA500,
+        ),
+        _ => return None,
+    };
+
+    Some(message)
+}
@@ -0,0 +1,325 @@
+use anyhow::Result;
+use chrono::{Local, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{error, warn};
+
+use crate::config::CONFIG;
+use crate::discord_webhook;
+use crate::discord_webhook::routing::{TARGETS, WebhookTarget};
+use crate::events::EVENT_BUS;
+use crate::vrchat::presence;
+use message::{Message, TIMESTAMP_FORMAT};
+use routing::{Filter, Route, Severity};
+
+pub mod matrix;
+pub mod message;
+pub mod ntfy;
+pub mod routing;
+pub mod slack;
+pub mod telegram;
+
+const MAX_ATTEMPTS: u32 = 5;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+static CHANNELS: OnceCell<Vec<Channel>> = OnceCell::new();
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Notifiers {
+    pub slack: Vec<slack::SlackNotifier>,
+    pub matrix: Vec<matrix::MatrixNotifier>,
+    pub telegram: Vec<telegram::TelegramNotifier>,
+    pub ntfy: Vec<ntfy::NtfyNotifier>,
+}
+
+/// A message on its way to the backends.
+pub struct Notification {
+    /// Unique per notification and kept across retries, so backends can deduplicate.
+    pub id: String,
+    pub message: Message,
+    pub severity: Severity,
+}
+
+impl Notification {
+    pub fn event(&self) -> &'static str {
+        self.message.event
+    }
+
+    pub fn title(&self) -> &str {
+        &self.message.title
+    }
+
+    pub fn body(&self) -> String {
+        self.message.text()
+    }
+}
+
+/// The backend asked to slow down, nothing should be sent to it before `0` has passed.
+#[derive(Debug, Error)]
+#[error("rate limited for {0:?}")]
+struct RateLimited(Duration);
+
+#[derive(Clone, Copy)]
+enum Backend {
+    Discord(&'static WebhookTarget),
+    Slack(&'static slack::SlackNotifier),
+    Matrix(&'static matrix::MatrixNotifier),
+    Telegram(&'static telegram::TelegramNotifier),
+    Ntfy(&'static ntfy::NtfyNotifier),
+}
+
+impl Backend {
+    fn name(&self) -> &'static str {
+        match self {
+            Backend::Discord(_) => "Discord",
+            Backend::Slack(_) => "Slack",
+            Backend::Matrix(_) => "Matrix",
+            Backend::Telegram(_) => "Telegram",
+            Backend::Ntfy(_) => "ntfy",
+        }
+    }
+
+    fn filter(&self) -> &Filter {
+        match self {
+            Backend::Discord(target) => &target.filter,
+            Backend::Slack(config) => &config.filter,
+            Backend::Matrix(config) => &config.filter,
+            Backend::Telegram(config) => &config.filter,
+            Backend::Ntfy(config) => &config.filter,
+        }
+    }
+
+    fn accepts(&self, route: &Route) -> bool {
+        let enabled = match self {
+            Backend::Discord(_) => discord_webhook::logs(route.event),
+            _ => true,
+        };
+
+        enabled && self.filter().accepts(route)
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        match self {
+            // The outbox batches, rate limits and retries Discord messages itself.
+            Backend::Discord(target) => {
+                discord_webhook::enqueue(target, &notification.message).await;
+                Ok(())
+            }
+            Backend::Slack(config) => slack::send(&CLIENT, config, notification).await,
+            Backend::Matrix(config) => matrix::send(&CLIENT, config, notification).await,
+            Backend::Telegram(config) => telegram::send(&CLIENT, config, notification).await,
+            Backend::Ntfy(config) => ntfy::send(&CLIENT, config, notification).await,
+        }
+    }
+}
+
+/// A configured backend. One worker sends its notifications in order, one at a time.
+struct Channel {
+    backend: Backend,
+    queue: mpsc::UnboundedSender<Arc<Notification>>,
+}
+
+impl Channel {
+    fn start(backend: Backend) -> Self {
+        let (queue, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run(backend, rx));
+
+        Self { backend, queue }
+    }
+}
+
+/// How long a rate limited response asks us to wait: the `Retry-After` header,
+/// or Telegram's `parameters.retry_after` in the body.
+async fn retry_after(response: reqwest::Response) -> Duration {
+    let header = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+    let body = response
+        .json::<Value>()
+        .await
+        .ok()
+        .and_then(|body| body["parameters"]["retry_after"].as_u64());
+
+    header.or(body).map_or(MIN_BACKOFF, Duration::from_secs)
+}
+
+/// Sends a request, leaving the URL out of errors since it can contain a token.
+async fn send_request(request: RequestBuilder) -> Result<()> {
+    let response = request.send().await.map_err(reqwest::Error::without_url)?;
+
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        return Err(RateLimited(retry_after(response).await).into());
+    }
+
+    response
+        .error_for_status()
+        .map_err(reqwest::Error::without_url)?;
+
+    Ok(())
+}
+
+fn backends() -> Vec<Backend> {
+    let settings = &CONFIG.notifiers;
+
+    let discord = TARGETS
+        .iter()
+        .filter(|_| CONFIG.discord_webhook.enabled)
+        .map(Backend::Discord);
+    let slack = settings.slack.iter().map(Backend::Slack);
+    let matrix = settings.matrix.iter().map(Backend::Matrix);
+    let telegram = settings.telegram.iter().map(Backend::Telegram);
+    let ntfy = settings.ntfy.iter().map(Backend::Ntfy);
+
+    discord
+        .chain(slack)
+        .chain(matrix)
+        .chain(telegram)
+        .chain(ntfy)
+        .collect()
+}
+
+async fn run(backend: Backend, mut queue: mpsc::UnboundedReceiver<Arc<Notification>>) {
+    // Until when the backend asked us to wait, carried over to the following notifications.
+    let mut blocked_until = None;
+
+    while let Some(notification) = queue.recv().await {
+        deliver(backend, &mut blocked_until, &notification).await;
+    }
+}
+
+async fn deliver(
+    backend: Backend,
+    blocked_until: &mut Option<Instant>,
+    notification: &Notification,
+) {
+    let mut backoff = MIN_BACKOFF;
+    let mut attempt = 0;
+
+    loop {
+        if let Some(until) = blocked_until.take() {
+            tokio::time::sleep_until(until).await;
+        }
+
+        let Err(e) = backend.send(notification).await else {
+            return;
+        };
+
+        // Rate limiting isn't the notification's fault, so it doesn't count as an attempt.
+        if let Some(RateLimited(wait)) = e.downcast_ref::<RateLimited>() {
+            warn!(
+                "{} rate limit hit, retrying in {}s",
+                backend.name(),
+                wait.as_secs()
+            );
+            *blocked_until = Some(Instant::now() + *wait);
+            continue;
+        }
+
+        attempt += 1;
+        if attempt >= MAX_ATTEMPTS {
+            error!(
+                "Dropping {} notification \"{}\" after {} attempts: {:#}",
+                backend.name(),
+                notification.title(),
+                attempt,
+                e
+            );
+            return;
+        }
+
+        warn!(
+            "{} notification failed, retrying in {}s: {:#}",
+            backend.name(),
+            backoff.as_secs(),
+            e
+        );
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+pub fn init() {
+    let channels = CHANNELS.get_or_init(|| backends().into_iter().map(Channel::start).collect());
+
+    if channels.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut rx = EVENT_BUS.subscribe().await;
+
+        while let Some(event) = rx.recv().await {
+            let Some(event_name) = message::event_name(&event) else {
+                continue;
+            };
+
+            // Events about a past session or another instance bring their own.
+            let instance = match message::instance(&event) {
+                Some(instance) => instance,
+                None => presence::current_location().await.unwrap_or_default(),
+            };
+            let world = instance.split(':').next().unwrap_or_default().to_string();
+
+            let route = Route {
+                event: event_name,
+                world: &world,
+            };
+            let targets = channels
+                .iter()
+                .filter(|channel| channel.backend.accepts(&route))
+                .collect::<Vec<_>>();
+
+            if targets.is_empty() {
+                continue;
+            }
+
+            let Some(mut message) = message::describe(&event) else {
+                continue;
+            };
+
+            let severity = route.severity();
+            message.vars.insert("instance", instance);
+            message.vars.insert("world", world);
+            message
+                .vars
+                .entry("timestamp")
+                .or_insert_with(|| Local::now().format(TIMESTAMP_FORMAT).to_string());
+
+            if let Some(template) = CONFIG.discord_webhook.templates.get(message.event) {
+                message = template.apply(message);
+            }
+
+            let notification = Arc::new(Notification {
+                id: format!(
+                    "{}-{}",
+                    Utc::now().timestamp_millis(),
+                    SEQUENCE.fetch_add(1, Ordering::Relaxed)
+                ),
+                message,
+                severity,
+            });
+
+            for channel in targets {
+                if channel.queue.send(notification.clone()).is_err() {
+                    error!(
+                        "{} notifier stopped, dropping a notification",
+                        channel.backend.name()
+                    );
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::routing::{Filter, Severity};
+use super::{Notification, send_request};
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct NtfyNotifier {
+    pub server: String,
+    pub topic: String,
+    pub token: Option<String>,
+    #[serde(flatten)]
+    pub filter: Filter,
+}
+
+impl Default for NtfyNotifier {
+    fn default() -> Self {
+        Self {
+            server: "https://ntfy.sh".into(),
+            topic: String::new(),
+            token: None,
+            filter: Filter::default(),
+        }
+    }
+}
+
+pub async fn send(
+    client: &Client,
+    config: &NtfyNotifier,
+    notification: &Notification,
+) -> Result<()> {
+    let priority = match notification.severity {
+        Severity::Info => 3,
+        Severity::Warning => 4,
+        Severity::Alert => 5,
+    };
+
+    // JSON publishing keeps non-ASCII display names out of HTTP headers.
+    let message = json!({
+        "topic": config.topic,
+        "title": notification.title(),
+        "message": notification.body(),
+        "priority": priority,
+        "tags": [notification.event()],
+    });
+
+    let mut request = client
+        .post(config.server.trim_end_matches('/'))
+        .json(&message);
+
+    if let Some(token) = &config.token {
+        request = request.bearer_auth(token);
+    }
+
+    send_request(request).await
+}
@@ -0,0 +1,77 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Alert,
+}
+
+pub struct Route<'a> {
+    pub event: &'a str,
+    pub world: &'a str,
+}
+
+impl Route<'_> {
+    pub fn severity(&self) -> Severity {
+        match self.event {
+            "auto_ban" | "watchlist_hit" | "blocklist_alert" => Severity::Alert,
+            "join_request_rejected"
+            | "join_request_escalated"
+            | "invite_quota"
+            | "ban_pending"
+            | "ban_failed"
+            | "group_user_banned"
+            | "group_member_removed"
+            | "blocklist_rolled_back" => Severity::Warning,
+            _ => Severity::Info,
+        }
+    }
+
+    /// The automation that produced the event, for routing e.g. all blocklist hits together.
+    pub fn category(&self) -> &'static str {
+        match self.event {
+            "auto_ban"
+            | "ban_pending"
+            | "ban_discarded"
+            | "ban_failed"
+            | "blocklist_alert"
+            | "blocklist_updated"
+            | "blocklist_rolled_back" => "blocklist",
+            "watchlist_hit" => "watchlist",
+            "auto_invite" | "invite_quota" => "invites",
+            "join_request_accepted" | "join_request_rejected" | "join_request_escalated" => {
+                "join_requests"
+            }
+            "role_granted" | "role_revoked" => "roles",
+            "session_report" | "weekly_digest" => "sessions",
+            event if event.starts_with("group_") => "group",
+            _ => "presence",
+        }
+    }
+}
+
+/// Which events a notification channel wants. Every filter left empty matches everything.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Filter {
+    pub events: Vec<String>,
+    pub categories: Vec<String>,
+    pub worlds: Vec<String>,
+    pub min_severity: Severity,
+}
+
+impl Filter {
+    pub fn accepts(&self, route: &Route) -> bool {
+        let allowed = |filter: &Vec<String>, value: &str| {
+            filter.is_empty() || filter.iter().any(|entry| entry == value)
+        };
+
+        route.severity() >= self.min_severity
+            && allowed(&self.events, route.event)
+            && allowed(&self.categories, route.category())
+            && allowed(&self.worlds, route.world)
+    }
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::routing::Filter;
+use super::{Notification, send_request};
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct SlackNotifier {
+    pub webhook_url: String,
+    #[serde(flatten)]
+    pub filter: Filter,
+}
+
+/// Escapes the three characters Slack's mrkdwn treats as control characters.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub async fn send(
+    client: &Client,
+    config: &SlackNotifier,
+    notification: &Notification,
+) -> Result<()> {
+    let text = format!(
+        "*{}*\n{}",
+        escape(notification.title()),
+        escape(&notification.body())
+    );
+
+    send_request(
+        client
+            .post(&config.webhook_url)
+            .json(&json!({ "text": text })),
+    )
+    .await
+}
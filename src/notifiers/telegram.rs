@@ -0,0 +1,36 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::routing::{Filter, Severity};
+use super::{Notification, send_request};
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct TelegramNotifier {
+    pub bot_token: String,
+    pub chat_id: String,
+    #[serde(flatten)]
+    pub filter: Filter,
+}
+
+pub async fn send(
+    client: &Client,
+    config: &TelegramNotifier,
+    notification: &Notification,
+) -> Result<()> {
+    let url = format!(
+        "https://api.telegram.org/bot{}/sendMessage",
+        config.bot_token
+    );
+
+    // Sent as plain text, so display names never need Markdown escaping.
+    let message = json!({
+        "chat_id": config.chat_id,
+        "text": format!("{}\n{}", notification.title(), notification.body()),
+        "disable_notification": notification.severity == Severity::Info,
+    });
+
+    send_request(client.post(url).json(&message)).await
+}
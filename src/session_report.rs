@@ -0,0 +1,371 @@
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::config::CONFIG;
+use crate::events::{AppEvent, EVENT_BUS};
+use crate::vrchat::{encounters, presence};
+use crate::{listen, storage};
+
+const REPORTS_FILE: &str = "session_reports.jsonl";
+const DIGEST_FILE: &str = "session_digest.json";
+const DIGEST_PERIOD: Duration = Duration::weeks(1);
+const DIGEST_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionReport {
+    pub location: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub peak_players: usize,
+    pub average_players: f64,
+    pub unique_players: usize,
+    pub new_players: usize,
+    pub returning_players: usize,
+    pub avatar_switches: u32,
+    pub bans: u32,
+    pub invites: u32,
+    pub watchlist_hits: Vec<String>,
+}
+
+impl SessionReport {
+    pub fn duration(&self) -> Duration {
+        self.ended_at - self.started_at
+    }
+}
+
+struct Tracker {
+    location: String,
+    started_at: DateTime<Utc>,
+    present: HashSet<String>,
+    seen: HashSet<String>,
+    last_change: DateTime<Utc>,
+    player_seconds: f64,
+    peak_players: usize,
+    avatar_switches: u32,
+    bans: u32,
+    invites: u32,
+    watchlisted: HashSet<String>,
+    watchlist_hits: Vec<String>,
+}
+
+impl Tracker {
+    fn new(location: String) -> Self {
+        let now = Utc::now();
+
+        Self {
+            location,
+            started_at: now,
+            present: HashSet::new(),
+            seen: HashSet::new(),
+            last_change: now,
+            player_seconds: 0.0,
+            peak_players: 0,
+            avatar_switches: 0,
+            bans: 0,
+            invites: 0,
+            watchlisted: HashSet::new(),
+            watchlist_hits: Vec::new(),
+        }
+    }
+
+    /// Accumulates player-time up to now, so the average is weighted by how long each count lasted.
+    fn sample(&mut self) {
+        let now = Utc::now();
+        let elapsed = (now - self.last_change).num_milliseconds().max(0) as f64 / 1000.0;
+        self.player_seconds += self.present.len() as f64 * elapsed;
+        self.last_change = now;
+    }
+
+    async fn finish(mut self) -> SessionReport {
+        self.sample();
+        let ended_at = self.last_change;
+
+        // We may have joined before the login told us who we are.
+        self.seen
+            .retain(|user_id| !presence::is_local_user(user_id));
+
+        // Anyone whose first encounter falls inside this session is meeting us for the first time.
+        let mut new_players = 0;
+        for user_id in &self.seen {
+            let first_seen = encounters::get(user_id).await.first_seen;
+            if first_seen.is_some_and(|first_seen| first_seen >= self.started_at) {
+                new_players += 1;
+            }
+        }
+
+        let seconds = (ended_at - self.started_at).num_milliseconds() as f64 / 1000.0;
+        let average_players = if seconds > 0.0 {
+            self.player_seconds / seconds
+        } else {
+            self.present.len() as f64
+        };
+
+        SessionReport {
+            location: self.location,
+            started_at: self.started_at,
+            ended_at,
+            peak_players: self.peak_players,
+            average_players,
+            unique_players: self.seen.len(),
+            new_players,
+            returning_players: self.seen.len() - new_players,
+            avatar_switches: self.avatar_switches,
+            bans: self.bans,
+            invites: self.invites,
+            watchlist_hits: self.watchlist_hits,
+        }
+    }
+}
+
+/// The session reports of a week added up, for those who don't read every one of them.
+#[derive(Clone, Debug)]
+pub struct WeeklyDigest {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub sessions: usize,
+    pub locations: usize,
+    pub time_in_instances: Duration,
+    pub peak_players: usize,
+    /// Unique players per session added up, someone met in two sessions counts twice.
+    pub players_met: usize,
+    pub new_players: usize,
+    pub avatar_switches: u32,
+    pub bans: u32,
+    pub invites: u32,
+    pub watchlist_hits: Vec<String>,
+}
+
+impl WeeklyDigest {
+    /// Sums up the reports of sessions that ended in `since..until`.
+    fn build(reports: &[SessionReport], since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        let reports = reports
+            .iter()
+            .filter(|report| report.ended_at > since && report.ended_at <= until)
+            .collect::<Vec<_>>();
+
+        let mut watchlist_hits = Vec::new();
+        for name in reports.iter().flat_map(|report| &report.watchlist_hits) {
+            if !watchlist_hits.contains(name) {
+                watchlist_hits.push(name.clone());
+            }
+        }
+
+        Self {
+            since,
+            until,
+            sessions: reports.len(),
+            locations: reports
+                .iter()
+                .map(|report| &report.location)
+                .collect::<HashSet<_>>()
+                .len(),
+            time_in_instances: reports.iter().map(|report| report.duration()).sum(),
+            peak_players: reports
+                .iter()
+                .map(|report| report.peak_players)
+                .max()
+                .unwrap_or(0),
+            players_met: reports.iter().map(|report| report.unique_players).sum(),
+            new_players: reports.iter().map(|report| report.new_players).sum(),
+            avatar_switches: reports.iter().map(|report| report.avatar_switches).sum(),
+            bans: reports.iter().map(|report| report.bans).sum(),
+            invites: reports.iter().map(|report| report.invites).sum(),
+            watchlist_hits,
+        }
+    }
+}
+
+/// When the last digest went out, so a restart neither skips nor repeats one.
+#[derive(Serialize, Deserialize, Default)]
+struct DigestState {
+    last_sent: Option<DateTime<Utc>>,
+}
+
+async fn send_digest_if_due() {
+    let mut state: DigestState = storage::load(DIGEST_FILE);
+    let now = Utc::now();
+
+    let Some(since) = state.last_sent else {
+        // The first digest covers the first full week.
+        state.last_sent = Some(now);
+        if let Err(e) = storage::save(DIGEST_FILE, &state) {
+            error!("Failed to save weekly digest state: {:#}", e);
+        }
+        return;
+    };
+
+    if now - since < DIGEST_PERIOD {
+        return;
+    }
+
+    let reports = storage::load_lines::<SessionReport>(REPORTS_FILE);
+    let digest = WeeklyDigest::build(&reports, since, now);
+
+    state.last_sent = Some(now);
+    if let Err(e) = storage::save(DIGEST_FILE, &state) {
+        // Sending it anyway could repeat it after every restart.
+        error!(
+            "Failed to save weekly digest state, not sending it: {:#}",
+            e
+        );
+        return;
+    }
+
+    info!(
+        "Weekly digest: {} sessions, {} players met",
+        digest.sessions, digest.players_met
+    );
+    EVENT_BUS.publish(AppEvent::OnWeeklyDigest(digest)).await;
+}
+
+static TRACKER: Lazy<Mutex<Option<Tracker>>> = Lazy::new(|| Mutex::new(None));
+
+async fn end_session(current: &mut Option<Tracker>) {
+    let Some(tracker) = current.take() else {
+        return;
+    };
+
+    if tracker.seen.is_empty() {
+        return;
+    }
+
+    let report = tracker.finish().await;
+
+    info!(
+        "Session in {} ended after {} minutes with {} unique players",
+        report.location,
+        report.duration().num_minutes(),
+        report.unique_players
+    );
+
+    if let Err(e) = storage::append(REPORTS_FILE, &report) {
+        error!("Failed to save session report: {:#}", e);
+    }
+
+    EVENT_BUS.publish(AppEvent::OnSessionEnded(report)).await;
+}
+
+pub fn init() {
+    if CONFIG.weekly_digest {
+        tokio::spawn(async move {
+            loop {
+                send_digest_if_due().await;
+                tokio::time::sleep(DIGEST_CHECK_INTERVAL).await;
+            }
+        });
+    }
+
+    listen!(
+        AppEvent::OnInstanceJoined(location) => {
+            let mut current = TRACKER.lock().await;
+            end_session(&mut current).await;
+            *current = Some(Tracker::new(location));
+        },
+        AppEvent::OnInstanceLeft => {
+            end_session(&mut *TRACKER.lock().await).await;
+        },
+        AppEvent::OnPlayerJoinedRaw(user_id) => {
+            // The log reports our own joins too, the roster and these counts leave us out.
+            if presence::is_local_user(&user_id) {
+                continue;
+            }
+
+            if let Some(tracker) = TRACKER.lock().await.as_mut() {
+                tracker.sample();
+                tracker.present.insert(user_id.clone());
+                tracker.seen.insert(user_id);
+                tracker.peak_players = tracker.peak_players.max(tracker.present.len());
+            }
+        },
+        AppEvent::OnPlayerLeftRaw(user_id) => {
+            if let Some(tracker) = TRACKER.lock().await.as_mut() {
+                tracker.sample();
+                tracker.present.remove(&user_id);
+            }
+        },
+        AppEvent::OnAvatarChangedRaw(user_id) => {
+            if presence::is_local_user(&user_id) {
+                continue;
+            }
+
+            if let Some(tracker) = TRACKER.lock().await.as_mut() {
+                tracker.avatar_switches += 1;
+            }
+        },
+        AppEvent::OnAutoBanned(..) => {
+            if let Some(tracker) = TRACKER.lock().await.as_mut() {
+                tracker.bans += 1;
+            }
+        },
+        AppEvent::OnAutoInvited(_) => {
+            if let Some(tracker) = TRACKER.lock().await.as_mut() {
+                tracker.invites += 1;
+            }
+        },
+        AppEvent::OnWatchlistHit(snapshot) => {
+            if let Some(tracker) = TRACKER.lock().await.as_mut()
+                && tracker.watchlisted.insert(snapshot.id().to_string())
+            {
+                tracker.watchlist_hits.push(snapshot.user.display_name);
+            }
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(location: &str, ended_at: DateTime<Utc>, watchlist_hits: &[&str]) -> SessionReport {
+        SessionReport {
+            location: location.to_string(),
+            started_at: ended_at - Duration::hours(1),
+            ended_at,
+            peak_players: 8,
+            average_players: 5.0,
+            unique_players: 10,
+            new_players: 3,
+            returning_players: 7,
+            avatar_switches: 4,
+            bans: 1,
+            invites: 2,
+            watchlist_hits: watchlist_hits.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn sums_reports_within_the_week() {
+        let until = Utc::now();
+        let since = until - DIGEST_PERIOD;
+        let reports = [
+            report("wrld_a", since - Duration::minutes(1), &["Mallory"]),
+            report("wrld_a", since + Duration::days(1), &["Eve"]),
+            report("wrld_b", until, &["Eve", "Trudy"]),
+        ];
+
+        let digest = WeeklyDigest::build(&reports, since, until);
+
+        assert_eq!(digest.sessions, 2);
+        assert_eq!(digest.locations, 2);
+        assert_eq!(digest.time_in_instances, Duration::hours(2));
+        assert_eq!(digest.peak_players, 8);
+        assert_eq!(digest.players_met, 20);
+        assert_eq!(digest.new_players, 6);
+        assert_eq!((digest.bans, digest.invites), (2, 4));
+        assert_eq!(digest.watchlist_hits, ["Eve", "Trudy"]);
+    }
+
+    #[test]
+    fn empty_week() {
+        let until = Utc::now();
+        let digest = WeeklyDigest::build(&[], until - DIGEST_PERIOD, until);
+
+        assert_eq!(digest.sessions, 0);
+        assert_eq!(digest.peak_players, 0);
+        assert_eq!(digest.time_in_instances, Duration::zero());
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+use crate::config::CONFIG;
+
+/// A journal file is rewritten once it holds this many lines more than there are queued items.
+const COMPACT_AFTER: usize = 1000;
+
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(CONFIG.data_dir.as_deref().unwrap_or("data")).join(name)
+}
+
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    let path = path(name);
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            error!("Failed to parse {}: {e}", path.display());
+            T::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => T::default(),
+        Err(e) => {
+            error!("Failed to read {}: {e}", path.display());
+            T::default()
+        }
+    }
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let contents = serde_json::to_string_pretty(value).context("Failed to serialize data")?;
+    replace(&path(name), &contents)
+}
+
+/// Writes a temporary file and renames it over `path`, so a crash never leaves half a file.
+pub fn replace(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+
+    fs::write(&tmp_path, contents)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Appends `value` as a single JSON line, for logs that are only ever added to.
+pub fn append<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let path = path(name);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Failed to create data directory")?;
+    }
+
+    let line = serde_json::to_string(value).context("Failed to serialize data")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    writeln!(file, "{line}").with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Reads every line written by `append`, skipping ones that no longer parse.
+pub fn load_lines<T: DeserializeOwned>(name: &str) -> Vec<T> {
+    let path = path(name);
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            error!("Failed to read {}: {e}", path.display());
+            return Vec::new();
+        }
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                error!("Skipping unreadable line in {}: {e}", path.display());
+                None
+            }
+        })
+        .collect()
+}
+
+/// Something waiting in a `Journal` for delivery.
+pub trait Journaled: Serialize + DeserializeOwned + Clone {
+    type Id: Serialize + DeserializeOwned + PartialEq + Clone + Display;
+
+    fn id(&self) -> &Self::Id;
+    fn attempts_mut(&mut self) -> &mut u32;
+}
+
+/// A line of a journal file. Replaying them in order gives the queue.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", bound = "")]
+enum Record<T: Journaled> {
+    Queued(T),
+    Attempted { id: T::Id },
+    Removed { id: T::Id },
+}
+
+/// A queue mirrored by an append-only file, so a change never rewrites the whole queue.
+pub struct Journal<T: Journaled> {
+    name: String,
+    items: VecDeque<T>,
+    lines: usize,
+}
+
+impl<T: Journaled> Journal<T> {
+    pub fn load(name: String) -> Self {
+        let mut items = VecDeque::<T>::new();
+
+        for record in load_lines::<Record<T>>(&name) {
+            match record {
+                Record::Queued(item) => items.push_back(item),
+                Record::Attempted { id } => {
+                    if let Some(item) = items.iter_mut().find(|item| *item.id() == id) {
+                        *item.attempts_mut() += 1;
+                    }
+                }
+                Record::Removed { id } => items.retain(|item| *item.id() != id),
+            }
+        }
+
+        let mut journal = Self {
+            name,
+            items,
+            lines: 0,
+        };
+        journal.compact();
+        journal
+    }
+
+    pub fn items(&self) -> &VecDeque<T> {
+        &self.items
+    }
+
+    fn write(&mut self, record: &Record<T>) {
+        if let Err(e) = append(&self.name, record) {
+            error!("Failed to write {}: {:#}", self.name, e);
+        }
+
+        self.lines += 1;
+        if self.lines > self.items.len() + COMPACT_AFTER {
+            self.compact();
+        }
+    }
+
+    /// Rewrites the file with only the items still queued.
+    fn compact(&mut self) {
+        let mut contents = String::new();
+        for item in &self.items {
+            match serde_json::to_string(&Record::Queued(item.clone())) {
+                Ok(line) => {
+                    contents.push_str(&line);
+                    contents.push('\n');
+                }
+                Err(e) => error!("Failed to serialize {} in {}: {}", item.id(), self.name, e),
+            }
+        }
+
+        match replace(&path(&self.name), &contents) {
+            Ok(()) => self.lines = self.items.len(),
+            Err(e) => error!("Failed to compact {}: {:#}", self.name, e),
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.write(&Record::Queued(item.clone()));
+        self.items.push_back(item);
+    }
+
+    pub fn pop_front(&mut self) {
+        if let Some(item) = self.items.pop_front() {
+            self.write(&Record::Removed {
+                id: item.id().clone(),
+            });
+        }
+    }
+
+    /// Removes an item by ID, it may already be gone if it was dropped meanwhile.
+    pub fn remove(&mut self, id: &T::Id) {
+        let Some(index) = self.items.iter().position(|item| item.id() == id) else {
+            return;
+        };
+
+        if let Some(item) = self.items.remove(index) {
+            self.write(&Record::Removed {
+                id: item.id().clone(),
+            });
+        }
+    }
+
+    /// Counts a failed attempt on an item, returning how many it has had.
+    pub fn attempted(&mut self, id: &T::Id) -> u32 {
+        let Some(item) = self.items.iter_mut().find(|item| item.id() == id) else {
+            return 0;
+        };
+
+        *item.attempts_mut() += 1;
+        let attempts = *item.attempts_mut();
+        let id = item.id().clone();
+        self.write(&Record::Attempted { id });
+        attempts
+    }
+}
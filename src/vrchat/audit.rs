@@ -0,0 +1,409 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, TimeDelta, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use vrchatapi::apis::configuration::Configuration;
+use vrchatapi::apis::groups_api;
+use vrchatapi::models::{BanGroupMemberRequest, CreateGroupInviteRequest};
+
+use crate::config::CONFIG;
+use crate::storage;
+use crate::vrchat::pardons;
+
+const AUDIT_FILE: &str = "audit_log.jsonl";
+/// How far apart our record of an action and VRChat's audit log entry for it may be.
+const OWN_ACTION_WINDOW: TimeDelta = TimeDelta::minutes(2);
+
+/// A group action the manager can take, and undo.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Ban { user_id: String },
+    Unban { user_id: String },
+    Invite { user_id: String },
+    RevokeInvite { user_id: String },
+    GrantRole { user_id: String, role_id: String },
+    RevokeRole { user_id: String, role_id: String },
+}
+
+impl Action {
+    fn inverse(&self) -> Action {
+        match self.clone() {
+            Action::Ban { user_id } => Action::Unban { user_id },
+            Action::Unban { user_id } => Action::Ban { user_id },
+            Action::Invite { user_id } => Action::RevokeInvite { user_id },
+            Action::RevokeInvite { user_id } => Action::Invite { user_id },
+            Action::GrantRole { user_id, role_id } => Action::RevokeRole { user_id, role_id },
+            Action::RevokeRole { user_id, role_id } => Action::GrantRole { user_id, role_id },
+        }
+    }
+
+    async fn execute(&self, config: &Configuration, group_id: &str) -> Result<()> {
+        match self {
+            Action::Ban { user_id } => {
+                let request = BanGroupMemberRequest::new(user_id.clone());
+                groups_api::ban_group_member(config, group_id, request)
+                    .await
+                    .context("Failed to ban user")?;
+            }
+            Action::Unban { user_id } => {
+                groups_api::unban_group_member(config, group_id, user_id)
+                    .await
+                    .context("Failed to unban user")?;
+            }
+            Action::Invite { user_id } => {
+                let request = CreateGroupInviteRequest::new(user_id.clone());
+                groups_api::create_group_invite(config, group_id, request)
+                    .await
+                    .context("Failed to invite user")?;
+            }
+            Action::RevokeInvite { user_id } => {
+                groups_api::delete_group_invite(config, group_id, user_id)
+                    .await
+                    .context("Failed to revoke invite")?;
+            }
+            Action::GrantRole { user_id, role_id } => {
+                groups_api::add_group_member_role(config, group_id, user_id, role_id)
+                    .await
+                    .context("Failed to add group role")?;
+            }
+            Action::RevokeRole { user_id, role_id } => {
+                groups_api::remove_group_member_role(config, group_id, user_id, role_id)
+                    .await
+                    .context("Failed to remove group role")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Ban { user_id } => write!(f, "ban {user_id}"),
+            Action::Unban { user_id } => write!(f, "unban {user_id}"),
+            Action::Invite { user_id } => write!(f, "invite {user_id}"),
+            Action::RevokeInvite { user_id } => write!(f, "revoke invite of {user_id}"),
+            Action::GrantRole { user_id, role_id } => write!(f, "grant {role_id} to {user_id}"),
+            Action::RevokeRole { user_id, role_id } => {
+                write!(f, "revoke {role_id} from {user_id}")
+            }
+        }
+    }
+}
+
+/// One line of the audit log.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub id: u64,
+    #[serde(flatten)]
+    pub action: Action,
+    /// Who asked for it, e.g. `auto_ban`, `console` or `name on Discord`.
+    pub actor: String,
+    /// The automation rule behind it, `None` for manual actions.
+    pub rule: Option<String>,
+    pub reason: String,
+    pub error: Option<String>,
+    pub undoes: Option<u64>,
+    /// The evidence bundle saved for a ban, see `evidence::get`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence_id: Option<u64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Entry {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+static LOG: Lazy<Mutex<Vec<Entry>>> = Lazy::new(|| Mutex::new(storage::load_lines(AUDIT_FILE)));
+/// Entries being undone right now, so concurrent undos can't reverse one twice.
+static UNDOING: Lazy<Mutex<HashSet<u64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+/// Entries already matched to a group audit log entry by `claim_own`.
+static CLAIMED: Lazy<Mutex<HashSet<u64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+fn group_id() -> Result<String> {
+    CONFIG
+        .group_id
+        .clone()
+        .context("group_id config variable is not set")
+}
+
+async fn write(mut entry: Entry) -> Entry {
+    let mut log = LOG.lock().await;
+    entry.id = log.last().map_or(1, |last| last.id + 1);
+
+    if let Err(e) = storage::append(AUDIT_FILE, &entry) {
+        error!("Failed to write audit log: {:#}", e);
+    }
+
+    log.push(entry.clone());
+    entry
+}
+
+async fn run(
+    config: &Configuration,
+    action: Action,
+    actor: &str,
+    rule: Option<String>,
+    reason: &str,
+    undoes: Option<u64>,
+    evidence_id: Option<u64>,
+) -> Result<Entry> {
+    let result = match group_id() {
+        Ok(group_id) => action.execute(config, &group_id).await,
+        Err(e) => Err(e),
+    };
+
+    let entry = write(Entry {
+        id: 0,
+        action,
+        actor: actor.to_string(),
+        rule,
+        reason: reason.to_string(),
+        error: result.as_ref().err().map(|e| format!("{e:#}")),
+        undoes,
+        evidence_id,
+        timestamp: Utc::now(),
+    })
+    .await;
+
+    if entry.succeeded() && entry.rule.is_none() {
+        pardons::record_manual(&entry.action, actor).await;
+    }
+
+    result.map(|()| entry)
+}
+
+/// Runs a group action and records it, whether or not it worked.
+pub async fn perform(
+    config: &Configuration,
+    action: Action,
+    actor: &str,
+    rule: Option<String>,
+    reason: &str,
+) -> Result<Entry> {
+    run(config, action, actor, rule, reason, None, None).await
+}
+
+/// Like `perform`, keeping the ID of the evidence saved for the action on its entry.
+pub async fn perform_with_evidence(
+    config: &Configuration,
+    action: Action,
+    actor: &str,
+    rule: Option<String>,
+    reason: &str,
+    evidence_id: Option<u64>,
+) -> Result<Entry> {
+    run(config, action, actor, rule, reason, None, evidence_id).await
+}
+
+pub async fn entries() -> Vec<Entry> {
+    LOG.lock().await.clone()
+}
+
+/// The rule behind the latest successful automatic ban of a user.
+pub async fn last_ban_rule(user_id: &str) -> Option<String> {
+    LOG.lock()
+        .await
+        .iter()
+        .rev()
+        .filter(|entry| entry.succeeded())
+        .find(|entry| matches!(&entry.action, Action::Ban { user_id: id } if id == user_id))
+        .and_then(|entry| entry.rule.clone())
+}
+
+/// Whether the manager itself took `action` around `at`, as opposed to someone using the
+/// VRChat UI on the same account. Each of our entries accounts for one group audit log
+/// entry at most, the closest in time.
+pub async fn claim_own(action: &Action, at: DateTime<Utc>) -> bool {
+    let log = LOG.lock().await;
+    let mut claimed = CLAIMED.lock().await;
+
+    let closest = log
+        .iter()
+        .filter(|entry| entry.succeeded() && &entry.action == action)
+        .filter(|entry| !claimed.contains(&entry.id))
+        .filter(|entry| (entry.timestamp - at).abs() <= OWN_ACTION_WINDOW)
+        .min_by_key(|entry| (entry.timestamp - at).abs());
+
+    match closest {
+        Some(entry) => claimed.insert(entry.id),
+        None => false,
+    }
+}
+
+async fn is_undone(id: u64) -> bool {
+    LOG.lock()
+        .await
+        .iter()
+        .any(|entry| entry.undoes == Some(id) && entry.succeeded())
+}
+
+/// Reverses a single successful action.
+pub async fn undo(config: &Configuration, id: u64, actor: &str) -> Result<Entry> {
+    if !UNDOING.lock().await.insert(id) {
+        bail!("#{id} is already being undone");
+    }
+
+    let result = undo_claimed(config, id, actor).await;
+    UNDOING.lock().await.remove(&id);
+    result
+}
+
+async fn undo_claimed(config: &Configuration, id: u64, actor: &str) -> Result<Entry> {
+    let Some(entry) = LOG
+        .lock()
+        .await
+        .iter()
+        .find(|entry| entry.id == id)
+        .cloned()
+    else {
+        bail!("No audit entry #{id}");
+    };
+
+    if !entry.succeeded() {
+        bail!("#{id} failed, there is nothing to undo");
+    }
+    if is_undone(id).await {
+        bail!("#{id} was already undone");
+    }
+
+    let reason = format!("Undo of #{id} ({})", entry.action);
+    let undone = run(
+        config,
+        entry.action.inverse(),
+        actor,
+        None,
+        &reason,
+        Some(id),
+        None,
+    )
+    .await?;
+
+    info!("{} undid #{}: {}", actor, id, entry.action);
+    Ok(undone)
+}
+
+/// Whether `rule` is `pattern` itself or one of its sub-rules, e.g. `blocked_avatar:file_...`.
+fn rule_matches(rule: &str, pattern: &str) -> bool {
+    rule == pattern
+        || rule
+            .strip_prefix(pattern)
+            .is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Reverses every successful action of a rule between `since` and `until`.
+/// Returns the undo entries along with the failures.
+pub async fn undo_rule(
+    config: &Configuration,
+    rule: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    actor: &str,
+) -> (Vec<Entry>, Vec<(u64, anyhow::Error)>) {
+    let ids = LOG
+        .lock()
+        .await
+        .iter()
+        .filter(|entry| entry.succeeded() && entry.timestamp >= since && entry.timestamp <= until)
+        .filter(|entry| {
+            entry
+                .rule
+                .as_deref()
+                .is_some_and(|entry_rule| rule_matches(entry_rule, rule))
+        })
+        .map(|entry| entry.id)
+        .collect::<Vec<_>>();
+
+    let mut undone = Vec::new();
+    let mut failed = Vec::new();
+
+    // Newest first, so a later action on the same user is reversed before an earlier one.
+    for id in ids.into_iter().rev() {
+        if is_undone(id).await {
+            continue;
+        }
+
+        match undo(config, id, actor).await {
+            Ok(entry) => undone.push(entry),
+            Err(e) => failed.push((id, e)),
+        }
+    }
+
+    (undone, failed)
+}
+
+/// Parses an RFC 3339 timestamp or a duration before now such as `30m`, `2h` or `1d`.
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let unit = value.chars().last().context("Empty time")?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .with_context(|| format!("Invalid time: {value}"))?;
+
+    let duration = match unit {
+        's' => TimeDelta::try_seconds(amount),
+        'm' => TimeDelta::try_minutes(amount),
+        'h' => TimeDelta::try_hours(amount),
+        'd' => TimeDelta::try_days(amount),
+        _ => bail!("Invalid time: {value}"),
+    };
+
+    duration
+        .and_then(|duration| Utc::now().checked_sub_signed(duration))
+        .with_context(|| format!("Time out of range: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rfc3339() {
+        let time = parse_time("2026-03-01T12:00:00+02:00").unwrap();
+        assert_eq!(time.to_rfc3339(), "2026-03-01T10:00:00+00:00");
+    }
+
+    #[test]
+    fn parses_durations_before_now() {
+        for (value, duration) in [
+            ("30s", TimeDelta::seconds(30)),
+            ("30m", TimeDelta::minutes(30)),
+            ("2h", TimeDelta::hours(2)),
+            ("1d", TimeDelta::days(1)),
+            ("0m", TimeDelta::zero()),
+        ] {
+            let expected = Utc::now() - duration;
+            let parsed = parse_time(value).unwrap();
+            assert!((parsed - expected).abs() < TimeDelta::seconds(5), "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        for value in ["", "m", "1w", "abc", "1.5h", "d1", "2026-03-01", "1é"] {
+            assert!(parse_time(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_durations() {
+        for value in ["99999999999d", "9223372036854775807s", "-99999999999d"] {
+            let error = parse_time(value).unwrap_err();
+            assert!(
+                error.to_string().starts_with("Time out of range"),
+                "{value}"
+            );
+        }
+        assert!(parse_time("99999999999999999999s").is_err());
+    }
+}
@@ -13,12 +13,14 @@ pub async fn auth() -> Result<Configuration> {
     let cookie_filename = format!("{}.cookie", username);
     let cookie_jar = Arc::new(reqwest::cookie::Jar::default());
 
-    let mut config = Configuration::default();
-    config.user_agent = Some("VRCAutoBan/0.0.1 49620461+RavMda@users.noreply.github.com".into());
-    config.client = reqwest::Client::builder()
-        .cookie_provider(cookie_jar.clone())
-        .build()
-        .context("Failed to build HTTP client")?;
+    let mut config = Configuration {
+        user_agent: Some("VRCAutoBan/0.0.1 49620461+RavMda@users.noreply.github.com".into()),
+        client: reqwest::Client::builder()
+            .cookie_provider(cookie_jar.clone())
+            .build()
+            .context("Failed to build HTTP client")?,
+        ..Default::default()
+    };
 
     if try_existing_cookie(&username, &config, &cookie_jar).await? {
         return Ok(config);
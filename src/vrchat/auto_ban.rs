@@ -1,68 +1,279 @@
-use crate::config::CONFIG;
-use crate::events::{AppEvent, EVENT_BUS};
+use crate::blocklist::{parse_lines, remote};
+use crate::config::{CONFIG, Trust};
+use crate::events::{AppEvent, EVENT_BUS, UserSnapshot};
 use crate::listen;
-use crate::vrchat::util::extract_avatar_file_id;
+use crate::vrchat::audit::{self, Action};
+use crate::vrchat::evidence::{self, Match, Outcome};
+use crate::vrchat::{automation, avatar_info, ban_approval, pardons, util};
 use anyhow::{Context, Result};
 use std::collections::HashSet;
-use std::io::{self, BufRead};
+use std::io;
 use tracing::{error, info};
 use vrchatapi::apis;
 use vrchatapi::apis::configuration::Configuration;
-use vrchatapi::models::{BanGroupMemberRequest, User};
 
-async fn process_user(config: &Configuration, user_id: String, user: User) -> Result<()> {
-    let group_id = CONFIG
-        .group_id
-        .clone()
-        .context("group_id config variable not set")?;
+/// Where an avatar was found listed.
+struct Listing {
+    matched: Match,
+    reason: String,
+    /// Listed by a source trusted to ban, rather than one that needs a moderator.
+    blocked: bool,
+    alert_only: bool,
+}
+
+/// An ID the worn avatar can be listed under: its image file, the avatar itself or its author.
+struct Candidate {
+    id: String,
+    rule: &'static str,
+    reason: String,
+}
+
+/// Entries only a resolved file can match, as opposed to file IDs.
+fn needs_resolving(id: &str) -> bool {
+    id.starts_with("avtr_") || id.starts_with("usr_")
+}
+
+async fn candidates(
+    config: &Configuration,
+    file_id: &str,
+    blocked: &HashSet<String>,
+    review: &HashSet<String>,
+) -> Vec<Candidate> {
+    let mut candidates = vec![Candidate {
+        id: file_id.to_string(),
+        rule: "blocked_avatar",
+        reason: format!("Blocked avatar {}", file_id),
+    }];
+
+    // Resolving costs an API call, skip it when the file ID already wins or nothing could match.
+    if blocked.contains(file_id)
+        || !(blocked.iter().any(|id| needs_resolving(id))
+            || review.iter().any(|id| needs_resolving(id))
+            || remote::any_entry(needs_resolving).await)
+    {
+        return candidates;
+    }
+
+    let info = avatar_info::resolve(config, file_id).await;
+
+    if let Some(avatar_id) = info.avatar_id {
+        candidates.push(Candidate {
+            reason: format!("Blocked avatar {}", avatar_id),
+            id: avatar_id,
+            rule: "blocked_avatar",
+        });
+    }
+
+    if let Some(author_id) = info.author_id {
+        candidates.push(Candidate {
+            reason: format!("Avatar {} by blocked author {}", file_id, author_id),
+            id: author_id,
+            rule: "blocked_author",
+        });
+    }
+
+    candidates
+}
+
+async fn find_listing(config: &Configuration, file_id: &str) -> Result<Option<Listing>> {
+    let blocked = load_avatar_list().context("Failed to load avatar list")?;
+    let review = ban_approval::review_list()?;
+    let candidates = candidates(config, file_id, &blocked, &review).await;
+
+    if let Some(candidate) = candidates.iter().find(|c| blocked.contains(&c.id)) {
+        return Ok(Some(Listing {
+            matched: Match {
+                rule: candidate.rule.to_string(),
+                source: avatar_file(),
+                entry: candidate.id.clone(),
+            },
+            reason: candidate.reason.clone(),
+            blocked: true,
+            alert_only: false,
+        }));
+    }
+
+    if let Some(candidate) = candidates.iter().find(|c| review.contains(&c.id)) {
+        return Ok(Some(Listing {
+            matched: Match {
+                rule: "avatar_under_review".to_string(),
+                source: CONFIG
+                    .auto_ban
+                    .approval
+                    .review_file
+                    .clone()
+                    .unwrap_or_default(),
+                entry: candidate.id.clone(),
+            },
+            reason: candidate.reason.clone(),
+            blocked: false,
+            alert_only: false,
+        }));
+    }
+
+    let mut found = Vec::new();
+    for candidate in &candidates {
+        if let Some(listed) = remote::lookup(&candidate.id).await {
+            found.push((candidate, listed));
+        }
+    }
+    found.sort_by_key(|(_, listed)| listed.trust != Trust::Ban);
+
+    let Some((candidate, found)) = found.into_iter().next() else {
+        return Ok(None);
+    };
+    let reason = match &found.reason {
+        Some(listed_for) => format!(
+            "{} (listed by {}: {listed_for})",
+            candidate.reason, found.source
+        ),
+        None => format!("{} (listed by {})", candidate.reason, found.source),
+    };
+
+    Ok(Some(Listing {
+        matched: Match {
+            rule: format!("remote_blocklist:{}", found.source),
+            source: found.url,
+            entry: candidate.id.clone(),
+        },
+        reason,
+        blocked: found.trust == Trust::Ban,
+        alert_only: found.trust == Trust::Alert,
+    }))
+}
+
+/// Why wearing the avatar behind `file_id` would get someone banned or sent for approval,
+/// matched the same way as on joins. Listings that only alert don't count.
+pub async fn blocking_reason(config: &Configuration, file_id: &str) -> Result<Option<String>> {
+    Ok(find_listing(config, file_id)
+        .await?
+        .filter(|listing| !listing.alert_only)
+        .map(|listing| listing.reason))
+}
+
+async fn process_user(config: &Configuration, snapshot: UserSnapshot) -> Result<()> {
+    if automation::is_paused("auto_ban").await {
+        return Ok(());
+    }
 
-    let avatar_id = match extract_avatar_file_id(&user)? {
+    let avatar_id = match &snapshot.avatar_file_id {
         Some(id) => id,
         _ => return Ok(()),
     };
 
-    let banned_avatars = load_avatar_list().context("Failed to load avatar list")?;
+    let Some(listing) = find_listing(config, avatar_id).await? else {
+        return Ok(());
+    };
+
+    if pardons::is_ban_pardoned(snapshot.id(), &listing.matched.entry).await {
+        info!(
+            "Not banning {} for {}: a moderator unbanned them before",
+            snapshot.id(),
+            listing.matched.entry
+        );
+        return Ok(());
+    }
+
+    if listing.alert_only {
+        info!("{} wears {}: {}", snapshot.id(), avatar_id, listing.reason);
+        EVENT_BUS
+            .publish(AppEvent::OnBlocklistAlert(snapshot, listing.reason))
+            .await;
+        return Ok(());
+    }
 
-    if !banned_avatars.contains(&avatar_id) {
+    let needs_approval = ban_approval::required(listing.blocked);
+    // Rejoins while a ban awaits approval would only pile up unused evidence.
+    if needs_approval && ban_approval::is_pending(snapshot.id()).await {
         return Ok(());
     }
 
-    let ban_request = BanGroupMemberRequest::new(user_id.clone());
-    apis::groups_api::ban_group_member(config, group_id.as_str(), ban_request)
-        .await
-        .context("Failed to ban user")?;
+    let Listing {
+        matched, reason, ..
+    } = listing;
+    let rule = format!("{}:{}", matched.rule, matched.entry);
+    let evidence_id = evidence::try_collect(config, &snapshot, matched, &reason).await;
 
-    info!("Banned {} from the group", user_id);
+    if needs_approval {
+        if !ban_approval::submit(snapshot, reason, rule, evidence_id).await
+            && let Some(id) = evidence_id
+        {
+            evidence::resolve(id, Outcome::Discarded).await;
+        }
+        return Ok(());
+    }
+
+    let result = ban(config, snapshot, reason, &rule, "auto_ban", evidence_id).await;
+    if result.is_err()
+        && let Some(id) = evidence_id
+    {
+        evidence::resolve(id, Outcome::Failed).await;
+    }
+
+    result
+}
+
+/// Bans a matched user, `rule` names the blocklist entry for the audit log.
+pub async fn ban(
+    config: &Configuration,
+    snapshot: UserSnapshot,
+    reason: String,
+    rule: &str,
+    actor: &str,
+    evidence_id: Option<u64>,
+) -> Result<()> {
+    let user_id = snapshot.id();
+    let action = Action::Ban {
+        user_id: user_id.to_string(),
+    };
+    let entry = audit::perform_with_evidence(
+        config,
+        action,
+        actor,
+        Some(rule.to_string()),
+        &reason,
+        evidence_id,
+    )
+    .await?;
+
+    info!("Banned {} from the group (audit #{})", user_id, entry.id);
+
+    if let Some(id) = evidence_id {
+        evidence::resolve(id, Outcome::Banned).await;
+    }
 
     EVENT_BUS
-        .publish(AppEvent::OnAutoBanned(user_id, avatar_id))
+        .publish(AppEvent::OnAutoBanned(snapshot, reason))
         .await;
 
     Ok(())
 }
 
-fn load_avatar_list() -> io::Result<HashSet<String>> {
-    let avatar_file = CONFIG
+pub fn avatar_file() -> String {
+    CONFIG
         .avatars_file
         .clone()
-        .unwrap_or("avatars.txt".to_string());
+        .unwrap_or("avatars.txt".to_string())
+}
 
-    let file = std::fs::File::open(avatar_file.as_str())?;
-    let reader = io::BufReader::new(file);
-    Ok(reader.lines().filter_map(Result::ok).collect())
+pub fn load_avatar_list() -> io::Result<HashSet<String>> {
+    let contents = util::read_lossy(&avatar_file())?;
+    Ok(parse_lines(&contents).into_iter().collect())
 }
 
 pub fn auto_ban(auth_config: &apis::configuration::Configuration) {
+    if CONFIG.auto_ban.approval.enabled {
+        ban_approval::init(auth_config);
+    }
+
+    avatar_info::init(auth_config);
+
     let auth_config_clone = auth_config.clone();
 
     listen!(
-        AppEvent::OnPlayerJoined(user_id, user) => {
-          if let Err(err) = process_user(&auth_config_clone, user_id.clone(), user).await {
-            error!("Failed to process user {}, err: {:#}", user_id, err);
-          };
-        },
-        AppEvent::OnAvatarChanged(user_id, user) => {
-          if let Err(err) = process_user(&auth_config_clone, user_id.clone(), user).await {
+        AppEvent::OnPlayerJoined(snapshot) | AppEvent::OnAvatarChanged(snapshot) => {
+          let user_id = snapshot.id().to_string();
+          if let Err(err) = process_user(&auth_config_clone, snapshot).await {
             error!("Failed to process user {}, err: {:#}", user_id, err);
           };
         }
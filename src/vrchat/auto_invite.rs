@@ -1,80 +1,277 @@
 use crate::config::CONFIG;
-use crate::events::{AppEvent, EVENT_BUS};
+use crate::events::{AppEvent, EVENT_BUS, UserSnapshot};
 use crate::listen;
+use crate::vrchat::audit::{self, Action};
+use crate::vrchat::{automation, criteria, invite_quota, presence};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use once_cell::sync::Lazy;
 use rand::Rng;
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 use tracing::{error, info};
 use vrchatapi::apis;
 use vrchatapi::apis::configuration::Configuration;
-use vrchatapi::models::CreateGroupInviteRequest;
+use vrchatapi::models::User;
 
-async fn process_user(config: &Configuration, user_id: String) -> Result<()> {
-    let group_id = CONFIG
-        .group_id
-        .clone()
-        .context("group_id config variable is not set")?;
+const QUOTA_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
 
-    let invite_request = CreateGroupInviteRequest::new(user_id.clone());
-    apis::groups_api::create_group_invite(config, group_id.as_str(), invite_request)
+async fn is_eligible(config: &Configuration, user_id: &str, user: &User) -> Result<bool> {
+    match criteria::evaluate(config, &CONFIG.auto_invite.criteria, user).await? {
+        Some(rejection) => {
+            info!(
+                "Not inviting {} ({}): {}",
+                user.display_name, user_id, rejection
+            );
+            Ok(false)
+        }
+        None => Ok(true),
+    }
+}
+
+/// The user as they are now, or `None` when they no longer qualify.
+async fn recheck_user(config: &Configuration, user_id: &str) -> Result<Option<UserSnapshot>> {
+    if !presence::is_present(user_id).await {
+        info!("Not inviting {}: no longer in the instance", user_id);
+        return Ok(None);
+    }
+
+    let user = apis::users_api::get_user(config, user_id)
         .await
-        .context("Failed to invite user")?;
+        .context("Failed to fetch user")?;
+
+    if !is_eligible(config, user_id, &user).await? {
+        return Ok(None);
+    }
+
+    Ok(Some(UserSnapshot::new(user)))
+}
+
+async fn process_user(config: &Configuration, snapshot: UserSnapshot) -> Result<bool> {
+    let user_id = snapshot.id().to_string();
+
+    let snapshot = if CONFIG.auto_invite.recheck_before_invite {
+        match recheck_user(config, &user_id).await? {
+            Some(snapshot) => snapshot,
+            None => return Ok(false),
+        }
+    } else {
+        snapshot
+    };
+
+    let action = Action::Invite {
+        user_id: user_id.clone(),
+    };
+    let rule = Some("auto_invite".to_string());
+    audit::perform(
+        config,
+        action,
+        "auto_invite",
+        rule,
+        "Eligible for an invite",
+    )
+    .await?;
 
     info!("Invited {} to the group", user_id);
 
-    EVENT_BUS.publish(AppEvent::OnAutoInvited(user_id)).await;
+    EVENT_BUS.publish(AppEvent::OnAutoInvited(snapshot)).await;
+
+    Ok(true)
+}
+
+#[derive(Clone)]
+pub struct PendingInvite {
+    pub user_id: String,
+    pub display_name: String,
+    pub joined_at: DateTime<Local>,
+    pub due: DateTime<Local>,
+    pub location: Option<String>,
+    /// The user as they joined, carried into the invite event.
+    pub snapshot: UserSnapshot,
+}
+
+type PendingMap = HashMap<String, (PendingInvite, JoinHandle<()>)>;
+
+static PENDING: Lazy<Mutex<PendingMap>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static DISPATCH: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub async fn pending_invites() -> Vec<PendingInvite> {
+    let mut pending = PENDING
+        .lock()
+        .await
+        .values()
+        .map(|(invite, _)| invite.clone())
+        .collect::<Vec<_>>();
+
+    pending.sort_by_key(|invite| invite.due);
+    pending
+}
+
+/// Whether an invite was queued for an instance we have since left.
+async fn is_stale(invite: &PendingInvite) -> bool {
+    CONFIG.auto_invite.cancel_on_instance_change
+        && invite.location != presence::current_location().await
+}
+
+async fn next_due_invite() -> Option<PendingInvite> {
+    let now = Local::now();
+
+    PENDING
+        .lock()
+        .await
+        .values()
+        .map(|(invite, _)| invite)
+        .filter(|invite| invite.due <= now)
+        .min_by_key(|invite| {
+            if CONFIG.auto_invite.quota.prioritize_longest_stay {
+                invite.joined_at
+            } else {
+                invite.due
+            }
+        })
+        .cloned()
+}
+
+/// Sends every invite whose delay has elapsed, as long as the quota allows.
+/// Invites held back by the quota stay queued until the next dispatch.
+async fn dispatch(config: &Configuration) {
+    let _guard = DISPATCH.lock().await;
+
+    // Pending invites stay queued while paused and go out on the next dispatch after resuming.
+    if automation::is_paused("auto_invite").await {
+        return;
+    }
+
+    while let Some(invite) = next_due_invite().await {
+        let user_id = invite.user_id.clone();
+
+        if is_stale(&invite).await {
+            PENDING.lock().await.remove(&user_id);
+            info!("Dropped invite for {}: queued in another instance", user_id);
+            continue;
+        }
+
+        if !invite_quota::available().await {
+            break;
+        }
+
+        PENDING.lock().await.remove(&user_id);
+
+        match process_user(config, invite.snapshot).await {
+            Ok(true) => invite_quota::record().await,
+            Ok(false) => (),
+            Err(err) => error!("Failed to process user {}: {:#}", user_id, err),
+        }
+    }
+}
+
+async fn cancel_all(reason: &str) {
+    let mut pending = PENDING.lock().await;
+    if pending.is_empty() {
+        return;
+    }
 
-    Ok(())
+    for (_, (_, handle)) in pending.drain() {
+        handle.abort();
+    }
+
+    info!("Aborted all pending invite timers: {}", reason);
 }
 
 pub fn auto_invite(auth_config: &Configuration) {
     let auth_config_clone = auth_config.clone();
-    let handles = Arc::new(Mutex::new(HashMap::<String, JoinHandle<()>>::new()));
-
-    let handles_clone = handles.clone();
 
     listen!(
-        AppEvent::OnPlayerJoined(user_id, _) => {
-          let auth_config_clone = auth_config_clone.clone();
+        AppEvent::OnPlayerJoined(snapshot) => {
+          let user_id = snapshot.id().to_string();
+          let user = snapshot.user.clone();
 
-          let mut handles_guard = handles_clone.lock().await;
+          // Joins can be enriched after we already left the instance they happened in.
+          if CONFIG.auto_invite.cancel_on_instance_change && !presence::is_present(&user_id).await {
+              info!("Not inviting {}: no longer in the instance", user_id);
+              continue;
+          }
 
-          if let Some(handle) = handles_guard.remove(&user_id) {
-              handle.abort();
+          match is_eligible(&auth_config_clone, &user_id, &user).await {
+              Ok(true) => (),
+              Ok(false) => continue,
+              Err(err) => {
+                  error!("Failed to check eligibility of {}: {:#}", user_id, err);
+                  continue;
+              }
           }
 
-          let duration = rand::rng().random_range(CONFIG.auto_invite.delay_min..CONFIG.auto_invite.delay_max);
-          let sleep_duration = Duration::from_secs(duration as u64);
+          let auth_config_clone = auth_config_clone.clone();
 
-          let user_id_clone = user_id.clone();
-          let task_handles = handles_clone.clone();
-          let handle = tokio::spawn(async move {
-              tokio::time::sleep(sleep_duration).await;
+          let mut pending = PENDING.lock().await;
 
-              if let Err(err) = process_user(&auth_config_clone, user_id_clone.clone()).await {
-                  error!("Failed to process user {}: {:#}", user_id_clone, err);
+          let joined_at = match pending.remove(&user_id) {
+              Some((invite, handle)) => {
+                  handle.abort();
+                  invite.joined_at
               }
+              None => Local::now(),
+          };
+
+          let duration = rand::rng().random_range(CONFIG.auto_invite.delay_min..=CONFIG.auto_invite.delay_max);
+          let sleep_duration = Duration::from_secs(duration);
 
-              task_handles.lock().await.remove(&user_id_clone);
+          let handle = tokio::spawn(async move {
+              tokio::time::sleep(sleep_duration).await;
+              tokio::spawn(async move { dispatch(&auth_config_clone).await });
           });
 
-          handles_guard.insert(user_id, handle);
+          info!("Scheduled invite for {} ({}) in {}s", user.display_name, user_id, duration);
+
+          let invite = PendingInvite {
+              user_id: user_id.clone(),
+              display_name: user.display_name,
+              joined_at,
+              due: Local::now() + sleep_duration,
+              location: presence::current_location().await,
+              snapshot,
+          };
+
+          pending.insert(user_id, (invite, handle));
         }
     );
 
-    let handles_clone = handles.clone();
-
     listen!(
-        AppEvent::OnPlayerLeft(user_id, _) => {
-          let mut handles_guard = handles_clone.lock().await;
-          if let Some(handle) = handles_guard.remove(&user_id) {
+        AppEvent::OnPlayerLeft(snapshot) => {
+          let user_id = snapshot.id();
+          let mut pending = PENDING.lock().await;
+          if let Some((_, handle)) = pending.remove(user_id) {
               handle.abort();
               info!("Aborted invite timer for {}", user_id);
           }
         }
     );
+
+    listen!(
+        AppEvent::OnInstanceJoined(location) => {
+            invite_quota::start_session(Some(location)).await;
+        }
+    );
+
+    // Picks up invites that were held back once the hourly or daily window frees up.
+    let auth_config_clone = auth_config.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(QUOTA_RECHECK_INTERVAL).await;
+            dispatch(&auth_config_clone).await;
+        }
+    });
+
+    if CONFIG.auto_invite.cancel_on_instance_change {
+        listen!(
+            AppEvent::OnInstanceJoined(_) => {
+                cancel_all("joined another instance").await;
+            },
+            AppEvent::OnInstanceLeft => {
+                cancel_all("left the instance").await;
+            }
+        );
+    }
 }
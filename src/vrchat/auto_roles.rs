@@ -0,0 +1,163 @@
+use crate::config::{CONFIG, RoleAction, RoleRule};
+use crate::events::{AppEvent, EVENT_BUS, UserSnapshot};
+use crate::listen;
+use crate::vrchat::audit::{self, Action};
+use crate::vrchat::{auto_ban, automation, criteria, encounters, pardons};
+use anyhow::{Context, Result};
+use tracing::{error, info};
+use vrchatapi::apis;
+use vrchatapi::apis::configuration::Configuration;
+
+async fn rule_matches(
+    config: &Configuration,
+    rule: &RoleRule,
+    snapshot: &UserSnapshot,
+) -> Result<bool> {
+    let user = &snapshot.user;
+
+    if let Some(min_encounters) = rule.min_encounters
+        && encounters::get(&user.id).await.count < min_encounters
+    {
+        return Ok(false);
+    }
+
+    if let Some(min_hours) = rule.min_hours_present
+        && (encounters::get(&user.id).await.seconds_present as f64 / 3600.0) < min_hours
+    {
+        return Ok(false);
+    }
+
+    if rule.on_blocked_avatar {
+        let wearing_blocked = match &snapshot.avatar_file_id {
+            Some(file_id) => auto_ban::blocking_reason(config, file_id).await?.is_some(),
+            None => false,
+        };
+
+        if !wearing_blocked {
+            return Ok(false);
+        }
+    }
+
+    Ok(criteria::evaluate(config, &rule.criteria, user)
+        .await?
+        .is_none())
+}
+
+/// How a role rule shows up in the audit log, e.g. `auto_roles:grant:grol_...`.
+fn rule_name(rule: &RoleRule) -> String {
+    let action = match rule.action {
+        RoleAction::Grant => "grant",
+        RoleAction::Revoke => "revoke",
+    };
+    format!("auto_roles:{action}:{}", rule.role_id)
+}
+
+async fn process_user(config: &Configuration, snapshot: UserSnapshot) -> Result<()> {
+    let group_id = CONFIG
+        .group_id
+        .clone()
+        .context("group_id config variable is not set")?;
+
+    if automation::is_paused("auto_roles").await {
+        return Ok(());
+    }
+
+    let mut matched = Vec::new();
+    for rule in &CONFIG.auto_roles.rules {
+        if rule_matches(config, rule, &snapshot).await? {
+            matched.push(rule);
+        }
+    }
+
+    if matched.is_empty() {
+        return Ok(());
+    }
+
+    // Non-members can't hold roles, so there's nothing to do for them.
+    let user_id = snapshot.id();
+    let member = match apis::groups_api::get_group_member(config, &group_id, user_id).await {
+        Ok(member) => member,
+        Err(apis::Error::ResponseError(response))
+            if response.status == reqwest::StatusCode::NOT_FOUND =>
+        {
+            return Ok(());
+        }
+        Err(e) => return Err(e).context("Failed to fetch group member"),
+    };
+    let role_ids = member.role_ids.unwrap_or_default();
+
+    for rule in matched {
+        let has_role = role_ids.contains(&rule.role_id);
+
+        if pardons::is_role_pardoned(user_id, &rule.role_id).await {
+            continue;
+        }
+
+        match rule.action {
+            RoleAction::Grant if !has_role => {
+                let action = Action::GrantRole {
+                    user_id: user_id.to_string(),
+                    role_id: rule.role_id.clone(),
+                };
+                audit::perform(
+                    config,
+                    action,
+                    "auto_roles",
+                    Some(rule_name(rule)),
+                    "Matched a grant rule",
+                )
+                .await?;
+
+                info!("Granted role {} to {}", rule.role_id, user_id);
+
+                EVENT_BUS
+                    .publish(AppEvent::OnRoleGranted(
+                        snapshot.clone(),
+                        rule.role_id.clone(),
+                    ))
+                    .await;
+            }
+            RoleAction::Revoke if has_role => {
+                let action = Action::RevokeRole {
+                    user_id: user_id.to_string(),
+                    role_id: rule.role_id.clone(),
+                };
+                audit::perform(
+                    config,
+                    action,
+                    "auto_roles",
+                    Some(rule_name(rule)),
+                    "Matched a revoke rule",
+                )
+                .await?;
+
+                info!("Revoked role {} from {}", rule.role_id, user_id);
+
+                EVENT_BUS
+                    .publish(AppEvent::OnRoleRevoked(
+                        snapshot.clone(),
+                        rule.role_id.clone(),
+                    ))
+                    .await;
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn auto_roles(auth_config: &Configuration) {
+    let auth_config = auth_config.clone();
+
+    listen!(
+        AppEvent::OnPlayerJoined(snapshot)
+        | AppEvent::OnAvatarChanged(snapshot)
+        | AppEvent::OnPlayerLeft(snapshot) => {
+            let user_id = snapshot.id().to_string();
+            if let Err(err) = process_user(&auth_config, snapshot).await {
+                error!("Failed to update roles of {}: {:#}", user_id, err);
+            }
+        }
+    );
+}
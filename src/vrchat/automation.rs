@@ -0,0 +1,59 @@
+use anyhow::{Result, bail};
+use once_cell::sync::Lazy;
+use std::collections::BTreeSet;
+use tokio::sync::Mutex;
+
+use crate::events::{AppEvent, EVENT_BUS};
+
+pub const ALL: &str = "all";
+pub const AUTOMATIONS: [&str; 4] = ["auto_ban", "auto_invite", "auto_roles", "join_requests"];
+
+static PAUSED: Lazy<Mutex<BTreeSet<String>>> = Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+fn validate(name: &str) -> Result<()> {
+    if name != ALL && !AUTOMATIONS.contains(&name) {
+        bail!(
+            "Unknown automation {}, expected {} or one of {}",
+            name,
+            ALL,
+            AUTOMATIONS.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub async fn is_paused(name: &str) -> bool {
+    let paused = PAUSED.lock().await;
+    paused.contains(ALL) || paused.contains(name)
+}
+
+pub async fn paused() -> Vec<String> {
+    PAUSED.lock().await.iter().cloned().collect()
+}
+
+/// Stops the named automation (or all of them) from taking any action until resumed.
+pub async fn pause(name: &str) -> Result<()> {
+    validate(name)?;
+    PAUSED.lock().await.insert(name.to_string());
+    EVENT_BUS
+        .publish(AppEvent::OnAutomationChanged(paused().await))
+        .await;
+    Ok(())
+}
+
+/// Resumes the named automation. Resuming `all` clears every pause.
+pub async fn resume(name: &str) -> Result<()> {
+    validate(name)?;
+    {
+        let mut paused = PAUSED.lock().await;
+        if name == ALL {
+            paused.clear();
+        } else {
+            paused.remove(name);
+        }
+    }
+    EVENT_BUS
+        .publish(AppEvent::OnAutomationChanged(paused().await))
+        .await;
+    Ok(())
+}
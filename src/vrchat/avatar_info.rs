@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+use vrchatapi::apis;
+use vrchatapi::apis::configuration::Configuration;
+
+use crate::blocklist::remote;
+use crate::storage;
+use crate::vrchat::{auto_ban, ban_approval, util};
+
+const CACHE_FILE: &str = "avatar_cache.json";
+/// Thumbnails get re-uploaded, so mappings are looked up again after this long.
+const MAX_AGE: TimeDelta = TimeDelta::days(7);
+const REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
+/// Pause between avatar lookups, to stay well within the API's rate limits.
+const LOOKUP_DELAY: Duration = Duration::from_secs(1);
+
+/// What's known about the avatar an image file belongs to.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AvatarInfo {
+    pub avatar_id: Option<String>,
+    pub author_id: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    /// Image file ID to the avatar and author behind it.
+    files: HashMap<String, AvatarInfo>,
+    /// When each listed avatar was last looked up.
+    avatars: HashMap<String, DateTime<Utc>>,
+}
+
+impl Cache {
+    fn persist(&self) {
+        if let Err(e) = storage::save(CACHE_FILE, self) {
+            error!("Failed to save avatar cache: {:#}", e);
+        }
+    }
+}
+
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(|| Mutex::new(storage::load(CACHE_FILE)));
+static LAST_LOOKUP: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+
+/// Waits until `LOOKUP_DELAY` has passed since the previous lookup, joins and refreshes alike.
+async fn throttle() {
+    let mut last_lookup = LAST_LOOKUP.lock().await;
+
+    if let Some(last_lookup) = *last_lookup {
+        tokio::time::sleep_until(last_lookup + LOOKUP_DELAY).await;
+    }
+    *last_lookup = Some(Instant::now());
+}
+
+fn is_fresh(time: Option<DateTime<Utc>>) -> bool {
+    time.is_some_and(|time| Utc::now() - time < MAX_AGE)
+}
+
+/// The avatar and author behind an image file. The author comes from the file's metadata,
+/// the avatar is only known for avatars that are listed somewhere.
+pub async fn resolve(config: &Configuration, file_id: &str) -> AvatarInfo {
+    if let Some(info) = CACHE.lock().await.files.get(file_id)
+        && is_fresh(info.resolved_at)
+    {
+        return info.clone();
+    }
+
+    throttle().await;
+    let author_id = match apis::files_api::get_file(config, file_id).await {
+        Ok(file) => Some(file.owner_id),
+        Err(e) => {
+            warn!("Failed to look up the author of {}: {}", file_id, e);
+            None
+        }
+    };
+
+    let mut cache = CACHE.lock().await;
+    let info = cache.files.entry(file_id.to_string()).or_default();
+    info.author_id = author_id.or(info.author_id.take());
+    // Failed lookups are cached too, files that can't be read rarely become readable.
+    info.resolved_at = Some(Utc::now());
+
+    let info = info.clone();
+    cache.persist();
+    info
+}
+
+/// Maps a listed avatar's current image file to it, so wearers can be matched by avatar ID.
+async fn learn_avatar(config: &Configuration, avatar_id: &str) -> Result<()> {
+    throttle().await;
+    let avatar = apis::avatars_api::get_avatar(config, avatar_id)
+        .await
+        .context("Failed to fetch avatar")?;
+    let file_id = util::file_id_from_url(&avatar.image_url)?;
+
+    let mut cache = CACHE.lock().await;
+    cache.files.insert(
+        file_id,
+        AvatarInfo {
+            avatar_id: Some(avatar.id),
+            author_id: Some(avatar.author_id),
+            resolved_at: Some(Utc::now()),
+        },
+    );
+    cache.avatars.insert(avatar_id.to_string(), Utc::now());
+    cache.persist();
+
+    Ok(())
+}
+
+/// Avatar IDs in any of the lists auto_ban checks.
+async fn listed_avatars() -> HashSet<String> {
+    let mut ids = remote::entry_ids().await;
+
+    match auto_ban::load_avatar_list() {
+        Ok(list) => ids.extend(list),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        Err(e) => error!("Failed to load avatar list: {}", e),
+    }
+    match ban_approval::review_list() {
+        Ok(list) => ids.extend(list),
+        Err(e) => error!("Failed to load review list: {}", e),
+    }
+
+    ids.retain(|id| id.starts_with("avtr_"));
+    ids
+}
+
+async fn refresh(config: &Configuration) {
+    let listed = listed_avatars().await;
+    let stale = {
+        let cache = CACHE.lock().await;
+        listed
+            .into_iter()
+            .filter(|id| !is_fresh(cache.avatars.get(id).copied()))
+            .collect::<Vec<_>>()
+    };
+
+    if stale.is_empty() {
+        return;
+    }
+
+    info!("Looking up {} listed avatars", stale.len());
+    for avatar_id in stale {
+        if let Err(e) = learn_avatar(config, &avatar_id).await {
+            warn!("Failed to resolve {}: {:#}", avatar_id, e);
+            // Not retried before the next lookup is due, private avatars stay private.
+            CACHE.lock().await.avatars.insert(avatar_id, Utc::now());
+        }
+    }
+}
+
+pub fn init(auth_config: &Configuration) {
+    let auth_config = auth_config.clone();
+
+    tokio::spawn(async move {
+        loop {
+            refresh(&auth_config).await;
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
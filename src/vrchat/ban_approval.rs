@@ -0,0 +1,276 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+use vrchatapi::apis::configuration::Configuration;
+
+use crate::blocklist::parse_lines;
+use crate::config::{CONFIG, TimeoutAction};
+use crate::events::{AppEvent, EVENT_BUS, UserSnapshot};
+use crate::storage;
+use crate::vrchat::auto_ban;
+use crate::vrchat::evidence::{self, Outcome};
+
+const PENDING_FILE: &str = "pending_bans.json";
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_TIMEOUT_MINUTES: i64 = 60;
+/// How many times a ban approved by `on_timeout` is tried before it's given up on.
+const MAX_TIMEOUT_ATTEMPTS: u32 = 5;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingBan {
+    pub id: u64,
+    pub snapshot: UserSnapshot,
+    pub reason: String,
+    #[serde(default)]
+    pub rule: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub evidence_id: Option<u64>,
+    /// Failed attempts to carry out the ban after it timed out.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Queue {
+    next_id: u64,
+    pending: Vec<PendingBan>,
+}
+
+impl Queue {
+    fn persist(&self) {
+        if let Err(e) = storage::save(PENDING_FILE, self) {
+            error!("Failed to save pending bans: {:#}", e);
+        }
+    }
+
+    fn take(&mut self, id: u64) -> Option<PendingBan> {
+        let index = self.pending.iter().position(|ban| ban.id == id)?;
+        let ban = self.pending.remove(index);
+        self.persist();
+        Some(ban)
+    }
+
+    /// Counts a failed timeout approval and delays the next one, doubling the wait each time.
+    /// Returns the ban once it has run out of attempts.
+    fn retry_later(&mut self, id: u64) -> Option<PendingBan> {
+        let ban = self.pending.iter_mut().find(|ban| ban.id == id)?;
+        ban.attempts += 1;
+
+        if ban.attempts >= MAX_TIMEOUT_ATTEMPTS {
+            return self.take(id);
+        }
+
+        let wait = EXPIRY_CHECK_INTERVAL * 2u32.pow(ban.attempts - 1);
+        ban.expires_at = Utc::now() + ChronoDuration::from_std(wait).unwrap_or_default();
+        self.persist();
+        None
+    }
+}
+
+static QUEUE: Lazy<Mutex<Queue>> = Lazy::new(|| Mutex::new(storage::load(PENDING_FILE)));
+
+/// Entries of the review file, empty when approval or the file isn't set up.
+pub fn review_list() -> io::Result<HashSet<String>> {
+    let settings = &CONFIG.auto_ban.approval;
+
+    let Some(review_file) = settings.review_file.as_deref().filter(|_| settings.enabled) else {
+        return Ok(HashSet::new());
+    };
+
+    match std::fs::read_to_string(review_file) {
+        Ok(contents) => Ok(parse_lines(&contents).into_iter().collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether a match needs approval. `blocked` is set for matches from the main avatar list.
+pub fn required(blocked: bool) -> bool {
+    let settings = &CONFIG.auto_ban.approval;
+    settings.enabled && (settings.all || !blocked)
+}
+
+pub async fn pending() -> Vec<PendingBan> {
+    QUEUE.lock().await.pending.clone()
+}
+
+pub async fn is_pending(user_id: &str) -> bool {
+    QUEUE
+        .lock()
+        .await
+        .pending
+        .iter()
+        .any(|ban| ban.snapshot.id() == user_id)
+}
+
+/// Queues a ban for approval. Returns false if the user already has one pending.
+pub async fn submit(
+    snapshot: UserSnapshot,
+    reason: String,
+    rule: String,
+    evidence_id: Option<u64>,
+) -> bool {
+    let mut queue = QUEUE.lock().await;
+
+    if queue
+        .pending
+        .iter()
+        .any(|ban| ban.snapshot.id() == snapshot.id())
+    {
+        return false;
+    }
+
+    let timeout = match CONFIG.auto_ban.approval.timeout_minutes {
+        0 => DEFAULT_TIMEOUT_MINUTES,
+        minutes => minutes as i64,
+    };
+    let now = Utc::now();
+
+    queue.next_id += 1;
+    let ban = PendingBan {
+        id: queue.next_id,
+        snapshot,
+        reason,
+        rule,
+        created_at: now,
+        expires_at: now + ChronoDuration::minutes(timeout),
+        evidence_id,
+        attempts: 0,
+    };
+
+    info!(
+        "Ban #{} of {} ({}) awaits approval: {}",
+        ban.id,
+        ban.snapshot.user.display_name,
+        ban.snapshot.id(),
+        ban.reason
+    );
+
+    queue.pending.push(ban.clone());
+    queue.persist();
+    drop(queue);
+
+    EVENT_BUS.publish(AppEvent::OnBanPending(ban)).await;
+    true
+}
+
+pub async fn approve(config: &Configuration, id: u64, actor: &str) -> Result<PendingBan> {
+    let ban = QUEUE
+        .lock()
+        .await
+        .take(id)
+        .with_context(|| format!("No pending ban #{id}"))?;
+
+    let reason = format!("{} (approved by {})", ban.reason, actor);
+
+    if let Err(e) = auto_ban::ban(
+        config,
+        ban.snapshot.clone(),
+        reason,
+        &ban.rule,
+        actor,
+        ban.evidence_id,
+    )
+    .await
+    {
+        // Keep it around so the approval can simply be retried.
+        let mut queue = QUEUE.lock().await;
+        queue.pending.push(ban);
+        queue.persist();
+        return Err(e);
+    }
+
+    info!("Ban #{} approved by {}", ban.id, actor);
+    Ok(ban)
+}
+
+pub async fn discard(id: u64, actor: &str) -> Result<PendingBan> {
+    let ban = QUEUE
+        .lock()
+        .await
+        .take(id)
+        .with_context(|| format!("No pending ban #{id}"))?;
+
+    info!("Ban #{} discarded by {}", ban.id, actor);
+    if let Some(evidence_id) = ban.evidence_id {
+        evidence::resolve(evidence_id, Outcome::Discarded).await;
+    }
+
+    EVENT_BUS
+        .publish(AppEvent::OnBanDiscarded(ban.clone(), actor.to_string()))
+        .await;
+
+    Ok(ban)
+}
+
+async fn expire(config: &Configuration) {
+    let now = Utc::now();
+    let expired = QUEUE
+        .lock()
+        .await
+        .pending
+        .iter()
+        .filter(|ban| ban.expires_at <= now)
+        .map(|ban| ban.id)
+        .collect::<Vec<_>>();
+
+    for id in expired {
+        match CONFIG.auto_ban.approval.on_timeout {
+            TimeoutAction::Approve => {
+                if let Err(e) = approve(config, id, "timeout").await {
+                    give_up_or_retry(id, e).await;
+                }
+            }
+            TimeoutAction::Discard => {
+                if let Err(e) = discard(id, "timeout").await {
+                    error!("Failed to resolve expired ban #{}: {:#}", id, e);
+                }
+            }
+        }
+    }
+}
+
+async fn give_up_or_retry(id: u64, error: anyhow::Error) {
+    let mut queue = QUEUE.lock().await;
+    // Resolved by someone else in the meantime.
+    if !queue.pending.iter().any(|ban| ban.id == id) {
+        return;
+    }
+
+    let Some(ban) = queue.retry_later(id) else {
+        warn!("Timed out ban #{} failed, retrying later: {:#}", id, error);
+        return;
+    };
+    drop(queue);
+
+    error!(
+        "Giving up on timed out ban #{} after {} attempts: {:#}",
+        id, ban.attempts, error
+    );
+    if let Some(evidence_id) = ban.evidence_id {
+        evidence::resolve(evidence_id, Outcome::Failed).await;
+    }
+
+    EVENT_BUS
+        .publish(AppEvent::OnBanFailed(ban, format!("{error:#}")))
+        .await;
+}
+
+pub fn init(auth_config: &Configuration) {
+    let auth_config = auth_config.clone();
+
+    tokio::spawn(async move {
+        loop {
+            expire(&auth_config).await;
+            tokio::time::sleep(EXPIRY_CHECK_INTERVAL).await;
+        }
+    });
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use serde::Deserialize;
+use std::fmt;
+use thiserror::Error;
+use vrchatapi::apis::configuration::Configuration;
+use vrchatapi::models::User;
+
+use crate::vrchat::auto_ban;
+use crate::vrchat::util::extract_avatar_file_id;
+use crate::vrchat::watchlist::load_watchlist;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustRank {
+    Visitor,
+    New,
+    User,
+    Known,
+    Trusted,
+}
+
+impl TrustRank {
+    pub fn of(user: &User) -> Self {
+        let has_tag = |tag: &str| user.tags.iter().any(|t| t == tag);
+
+        if has_tag("system_trust_veteran") {
+            TrustRank::Trusted
+        } else if has_tag("system_trust_trusted") {
+            TrustRank::Known
+        } else if has_tag("system_trust_known") {
+            TrustRank::User
+        } else if has_tag("system_trust_basic") {
+            TrustRank::New
+        } else {
+            TrustRank::Visitor
+        }
+    }
+}
+
+impl fmt::Display for TrustRank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TrustRank::Visitor => "Visitor",
+            TrustRank::New => "New User",
+            TrustRank::User => "User",
+            TrustRank::Known => "Known User",
+            TrustRank::Trusted => "Trusted User",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Criteria {
+    pub min_trust_rank: Option<TrustRank>,
+    pub min_account_age_days: Option<i64>,
+    pub reject_watchlisted: bool,
+    pub reject_blocked_avatar: bool,
+    pub platforms: Vec<String>,
+}
+
+impl Criteria {
+    /// Whether no check is configured, so every user passes.
+    pub fn is_empty(&self) -> bool {
+        self.min_trust_rank.is_none()
+            && self.min_account_age_days.is_none()
+            && !self.reject_watchlisted
+            && !self.reject_blocked_avatar
+            && self.platforms.is_empty()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Rejection {
+    #[error("trust rank {actual} is below {required}")]
+    TrustRank {
+        actual: TrustRank,
+        required: TrustRank,
+    },
+    #[error("account is {actual} days old, {required} required")]
+    AccountAge { actual: i64, required: i64 },
+    #[error("user is on the watchlist")]
+    Watchlisted,
+    #[error("user is wearing a blocked avatar: {0}")]
+    BlockedAvatar(String),
+    #[error("platform {0} is not allowed")]
+    Platform(String),
+}
+
+pub fn account_age_days(user: &User) -> Option<i64> {
+    let joined = NaiveDate::parse_from_str(&user.date_joined, "%Y-%m-%d").ok()?;
+    Some((Local::now().date_naive() - joined).num_days())
+}
+
+pub async fn evaluate(
+    config: &Configuration,
+    criteria: &Criteria,
+    user: &User,
+) -> Result<Option<Rejection>> {
+    if let Some(required) = criteria.min_trust_rank {
+        let actual = TrustRank::of(user);
+        if actual < required {
+            return Ok(Some(Rejection::TrustRank { actual, required }));
+        }
+    }
+
+    if let Some(required) = criteria.min_account_age_days {
+        let actual = account_age_days(user).unwrap_or(0);
+        if actual < required {
+            return Ok(Some(Rejection::AccountAge { actual, required }));
+        }
+    }
+
+    if !criteria.platforms.is_empty() && !criteria.platforms.contains(&user.last_platform) {
+        return Ok(Some(Rejection::Platform(user.last_platform.clone())));
+    }
+
+    if criteria.reject_watchlisted && load_watchlist()?.contains(&user.id) {
+        return Ok(Some(Rejection::Watchlisted));
+    }
+
+    if criteria.reject_blocked_avatar
+        && let Some(file_id) = extract_avatar_file_id(user)?
+        && let Some(reason) = auto_ban::blocking_reason(config, &file_id).await?
+    {
+        return Ok(Some(Rejection::BlockedAvatar(reason)));
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tracing::error;
+
+use crate::{events::AppEvent, listen, storage};
+
+const STATS_FILE: &str = "encounters.json";
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Encounters {
+    pub count: u32,
+    pub seconds_present: u64,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct State {
+    stats: HashMap<String, Encounters>,
+    joined_at: HashMap<String, DateTime<Utc>>,
+}
+
+impl State {
+    fn end_visit(&mut self, user_id: &str) {
+        if let Some(joined_at) = self.joined_at.remove(user_id) {
+            let now = Utc::now();
+            let entry = self.stats.entry(user_id.to_string()).or_default();
+            entry.seconds_present += (now - joined_at).num_seconds().max(0) as u64;
+            entry.last_seen = Some(now);
+        }
+    }
+
+    fn persist(&self) {
+        if let Err(e) = storage::save(STATS_FILE, &self.stats) {
+            error!("Failed to save encounter stats: {:#}", e);
+        }
+    }
+}
+
+static STATE: Lazy<Mutex<State>> = Lazy::new(|| {
+    Mutex::new(State {
+        stats: storage::load(STATS_FILE),
+        joined_at: HashMap::new(),
+    })
+});
+
+/// Returns the stored stats for a user, including time spent in the current visit.
+pub async fn get(user_id: &str) -> Encounters {
+    let state = STATE.lock().await;
+    let mut stats = state.stats.get(user_id).cloned().unwrap_or_default();
+
+    if let Some(joined_at) = state.joined_at.get(user_id) {
+        stats.seconds_present += (Utc::now() - *joined_at).num_seconds().max(0) as u64;
+    }
+
+    stats
+}
+
+pub fn init() {
+    listen!(
+        AppEvent::OnPlayerJoinedRaw(user_id) => {
+            let mut state = STATE.lock().await;
+            let now = Utc::now();
+
+            let entry = state.stats.entry(user_id.clone()).or_default();
+            entry.count += 1;
+            entry.first_seen.get_or_insert(now);
+            entry.last_seen = Some(now);
+
+            state.joined_at.insert(user_id, now);
+            state.persist();
+        },
+        AppEvent::OnPlayerLeftRaw(user_id) => {
+            let mut state = STATE.lock().await;
+            state.end_visit(&user_id);
+            state.persist();
+        },
+        AppEvent::OnInstanceJoined(_) | AppEvent::OnInstanceLeft => {
+            let mut state = STATE.lock().await;
+            let present = state.joined_at.keys().cloned().collect::<Vec<_>>();
+            for user_id in present {
+                state.end_visit(&user_id);
+            }
+            state.persist();
+        }
+    );
+}
@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+use vrchatapi::apis::configuration::Configuration;
+
+use crate::config::CONFIG;
+use crate::events::UserSnapshot;
+use crate::log_parser;
+use crate::storage;
+
+const INDEX_FILE: &str = "evidence/index.json";
+const LOG_CONTEXT_LINES: usize = 20;
+
+/// What made a user a ban candidate.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Match {
+    pub rule: String,
+    pub source: String,
+    pub entry: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pending,
+    Banned,
+    Discarded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bundle {
+    pub id: u64,
+    pub user_id: String,
+    pub display_name: String,
+    pub avatar_file_id: Option<String>,
+    pub reason: String,
+    #[serde(rename = "match")]
+    pub matched: Match,
+    pub outcome: Outcome,
+    pub thumbnail_url: String,
+    pub thumbnail_file: Option<String>,
+    pub log_lines: usize,
+    pub captured_at: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Bundle {
+    fn file(&self, name: &str) -> String {
+        format!("evidence/{}/{}", self.id, name)
+    }
+
+    fn persist(&self) {
+        if let Err(e) = storage::save(&self.file("bundle.json"), self) {
+            error!("Failed to save evidence bundle #{}: {:#}", self.id, e);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    next_id: u64,
+    bundles: Vec<Bundle>,
+}
+
+impl Index {
+    fn persist(&self) {
+        if let Err(e) = storage::save(INDEX_FILE, self) {
+            error!("Failed to save evidence index: {:#}", e);
+        }
+    }
+}
+
+static INDEX: Lazy<Mutex<Index>> = Lazy::new(|| Mutex::new(storage::load(INDEX_FILE)));
+
+pub async fn get(id: u64) -> Option<Bundle> {
+    let index = INDEX.lock().await;
+    index.bundles.iter().find(|bundle| bundle.id == id).cloned()
+}
+
+fn write_file(name: &str, contents: &[u8]) -> Result<()> {
+    let path = storage::path(name);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Failed to create evidence directory")?;
+    }
+
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+async fn download_thumbnail(config: &Configuration, url: &str) -> Result<(Vec<u8>, String)> {
+    let mut request = config.client.get(url);
+    if let Some(user_agent) = &config.user_agent {
+        request = request.header(USER_AGENT, user_agent);
+    }
+
+    let response = request.send().await?.error_for_status()?;
+    let extension = match response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some("image/jpeg") => "jpg",
+        Some("image/webp") => "webp",
+        _ => "png",
+    };
+
+    Ok((response.bytes().await?.to_vec(), extension.to_string()))
+}
+
+/// Saves everything known about a ban candidate right now and returns the bundle ID.
+pub async fn collect(
+    config: &Configuration,
+    snapshot: &UserSnapshot,
+    matched: Match,
+    reason: &str,
+) -> Result<u64> {
+    let user = &snapshot.user;
+
+    let id = {
+        let mut index = INDEX.lock().await;
+        index.next_id += 1;
+        index.persist();
+        index.next_id
+    };
+
+    let mut bundle = Bundle {
+        id,
+        user_id: user.id.clone(),
+        display_name: user.display_name.clone(),
+        avatar_file_id: snapshot.avatar_file_id.clone(),
+        reason: reason.to_string(),
+        matched,
+        outcome: Outcome::Pending,
+        thumbnail_url: user.current_avatar_thumbnail_image_url.clone(),
+        thumbnail_file: None,
+        log_lines: 0,
+        captured_at: snapshot.captured_at,
+        detected_at: Utc::now(),
+        resolved_at: None,
+    };
+
+    storage::save(&bundle.file("user.json"), snapshot)?;
+
+    let log_lines = log_parser::excerpt(&[&user.id, &user.display_name], LOG_CONTEXT_LINES).await;
+    bundle.log_lines = log_lines.len();
+    write_file(
+        &bundle.file("log.txt"),
+        (log_lines.join("\n") + "\n").as_bytes(),
+    )?;
+
+    if !bundle.thumbnail_url.is_empty() {
+        match download_thumbnail(config, &bundle.thumbnail_url).await {
+            Ok((bytes, extension)) => {
+                let name = format!("thumbnail.{extension}");
+                write_file(&bundle.file(&name), &bytes)?;
+                bundle.thumbnail_file = Some(name);
+            }
+            Err(e) => warn!("Failed to download avatar thumbnail for evidence #{id}: {e:#}"),
+        }
+    }
+
+    bundle.persist();
+
+    let mut index = INDEX.lock().await;
+    index.bundles.push(bundle);
+    index.persist();
+
+    info!(
+        "Saved evidence #{} for {} to {}",
+        id,
+        user.id,
+        storage::path(&format!("evidence/{id}")).display()
+    );
+
+    Ok(id)
+}
+
+/// Collects evidence when enabled, logging instead of failing so the ban itself goes ahead.
+pub async fn try_collect(
+    config: &Configuration,
+    snapshot: &UserSnapshot,
+    matched: Match,
+    reason: &str,
+) -> Option<u64> {
+    if !CONFIG.auto_ban.evidence {
+        return None;
+    }
+
+    match collect(config, snapshot, matched, reason).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            error!("Failed to collect evidence for {}: {:#}", snapshot.id(), e);
+            None
+        }
+    }
+}
+
+pub async fn resolve(id: u64, outcome: Outcome) {
+    let mut index = INDEX.lock().await;
+
+    let Some(bundle) = index.bundles.iter_mut().find(|bundle| bundle.id == id) else {
+        return;
+    };
+
+    bundle.outcome = outcome;
+    bundle.resolved_at = Some(Utc::now());
+    bundle.persist();
+    index.persist();
+}
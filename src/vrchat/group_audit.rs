@@ -0,0 +1,335 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{error, warn};
+use vrchatapi::apis;
+use vrchatapi::apis::configuration::Configuration;
+use vrchatapi::models::{GroupAuditLogEntry, PaginatedGroupAuditLogEntryList};
+
+use crate::config::CONFIG;
+use crate::events::{AppEvent, EVENT_BUS};
+use crate::storage;
+use crate::vrchat::audit::{self, Action};
+use crate::vrchat::presence;
+
+const CURSOR_FILE: &str = "group_audit_cursor.json";
+const PAGE_SIZE: i32 = 100;
+const MAX_PAGES: usize = 10;
+/// How soon to poll again after a poll left entries over.
+const CATCH_UP_DELAY: Duration = Duration::from_secs(5);
+
+/// An entry of the group's audit log, whoever in the group it was done by.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GroupActivity {
+    pub id: String,
+    pub event_type: String,
+    pub actor_id: Option<String>,
+    pub actor_display_name: Option<String>,
+    /// Usually a user ID, but a location for instances or a post ID for posts.
+    pub target_id: Option<String>,
+    pub description: String,
+    pub data: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    /// Done by the manager itself, rather than by a person in the VRChat UI.
+    pub by_manager: bool,
+}
+
+impl GroupActivity {
+    /// The event name used for routing and templates, e.g. `group_user_banned`.
+    pub fn event_name(&self) -> &'static str {
+        match self.event_type.as_str() {
+            "group.member.join" => "group_member_joined",
+            "group.member.leave" => "group_member_left",
+            "group.member.remove" => "group_member_removed",
+            "group.user.ban" => "group_user_banned",
+            "group.user.unban" => "group_user_unbanned",
+            "group.member.role.assign" => "group_role_assigned",
+            "group.member.role.unassign" => "group_role_unassigned",
+            "group.post.create" => "group_post_created",
+            "group.instance.create" => "group_instance_created",
+            _ => "group_activity",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self.event_name() {
+            "group_member_joined" => "Member Joined the Group",
+            "group_member_left" => "Member Left the Group",
+            "group_member_removed" => "Member Removed",
+            "group_user_banned" => "User Banned from the Group",
+            "group_user_unbanned" => "User Unbanned",
+            "group_role_assigned" => "Role Assigned",
+            "group_role_unassigned" => "Role Removed",
+            "group_post_created" => "New Group Post",
+            "group_instance_created" => "Group Instance Opened",
+            _ => "Group Activity",
+        }
+    }
+
+    pub fn actor(&self) -> &str {
+        self.actor_display_name.as_deref().unwrap_or("Someone")
+    }
+
+    /// VRChat's own description, or a plain one when it has none.
+    pub fn summary(&self) -> String {
+        if !self.description.is_empty() {
+            return self.description.clone();
+        }
+
+        match &self.target_id {
+            Some(target) => format!("{} ({} on {})", self.title(), self.actor(), target),
+            None => format!("{} ({})", self.title(), self.actor()),
+        }
+    }
+
+    /// The first role ID mentioned in the entry's data, for role changes.
+    pub fn role_id(&self) -> Option<String> {
+        fn find(value: &Value) -> Option<String> {
+            match value {
+                Value::String(s) if s.starts_with("grol_") => Some(s.clone()),
+                Value::Array(values) => values.iter().find_map(find),
+                Value::Object(map) => map.values().find_map(find),
+                _ => None,
+            }
+        }
+
+        self.data.as_ref().and_then(find)
+    }
+
+    /// The group action this entry records, for the ones the manager can take itself.
+    pub fn action(&self) -> Option<Action> {
+        let user_id = self.target_id.clone()?;
+
+        let action = match self.event_name() {
+            "group_user_banned" => Action::Ban { user_id },
+            "group_user_unbanned" => Action::Unban { user_id },
+            "group_role_assigned" => Action::GrantRole {
+                user_id,
+                role_id: self.role_id()?,
+            },
+            "group_role_unassigned" => Action::RevokeRole {
+                user_id,
+                role_id: self.role_id()?,
+            },
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    fn into_event(self) -> Option<AppEvent> {
+        let event = match self.event_name() {
+            "group_member_joined" => AppEvent::OnGroupMemberJoined(self),
+            "group_member_left" => AppEvent::OnGroupMemberLeft(self),
+            "group_member_removed" => AppEvent::OnGroupMemberRemoved(self),
+            "group_user_banned" => AppEvent::OnGroupUserBanned(self),
+            "group_user_unbanned" => AppEvent::OnGroupUserUnbanned(self),
+            "group_role_assigned" => AppEvent::OnGroupRoleAssigned(self),
+            "group_role_unassigned" => AppEvent::OnGroupRoleUnassigned(self),
+            "group_post_created" => AppEvent::OnGroupPostCreated(self),
+            "group_instance_created" => AppEvent::OnGroupInstanceCreated(self),
+            _ => return None,
+        };
+
+        Some(event)
+    }
+}
+
+/// Where the last poll stopped. Entries at exactly `cursor` come back on the next poll,
+/// so their IDs are kept to skip them.
+#[derive(Serialize, Deserialize, Default)]
+struct Cursor {
+    cursor: Option<DateTime<Utc>>,
+    seen: HashSet<String>,
+}
+
+fn created_at(entry: &GroupAuditLogEntry) -> Option<DateTime<Utc>> {
+    let created_at = entry.created_at.as_deref()?;
+    DateTime::parse_from_rfc3339(created_at)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn to_activity(entry: GroupAuditLogEntry) -> Option<GroupActivity> {
+    let created_at = created_at(&entry)?;
+
+    Some(GroupActivity {
+        id: entry.id?,
+        event_type: entry.event_type?,
+        actor_id: entry.actor_id,
+        actor_display_name: entry.actor_display_name,
+        target_id: entry.target_id,
+        description: entry.description.unwrap_or_default(),
+        data: entry.data,
+        created_at,
+        by_manager: false,
+    })
+}
+
+async fn fetch_page(
+    config: &Configuration,
+    group_id: &str,
+    since: DateTime<Utc>,
+    offset: usize,
+) -> Result<PaginatedGroupAuditLogEntryList> {
+    apis::groups_api::get_group_audit_logs(
+        config,
+        group_id,
+        Some(PAGE_SIZE),
+        Some(offset as i32),
+        Some(since.to_rfc3339()),
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .context("Failed to fetch group audit log")
+}
+
+/// Fetches entries since `since`, oldest first. When there are more than `MAX_PAGES` pages,
+/// only the oldest ones are returned along with `true`, the rest are left for the next poll.
+async fn fetch_since(
+    config: &Configuration,
+    group_id: &str,
+    since: DateTime<Utc>,
+) -> Result<(Vec<GroupAuditLogEntry>, bool)> {
+    let limit = MAX_PAGES * PAGE_SIZE as usize;
+
+    let first = fetch_page(config, group_id, since, 0).await?;
+    let mut has_next = first.has_next.unwrap_or(false);
+    let mut entries = first.results.unwrap_or_default();
+    let mut offset = PAGE_SIZE as usize;
+    let mut skipped = false;
+
+    let newest_first = entries.first().and_then(created_at) > entries.last().and_then(created_at);
+
+    // Newest first, the oldest entries are on the last pages, so skip straight to them.
+    if newest_first
+        && let Some(total) = first.total_count.map(|total| total.max(0) as usize)
+        && total > limit
+    {
+        entries.clear();
+        offset = total - limit;
+        skipped = true;
+    }
+
+    // Oldest first, the first pages are all we need. Newest first, new entries shift the
+    // pages while we read them, so keep going until the end rather than trusting the total.
+    while has_next && (newest_first || entries.len() < limit) {
+        let page = fetch_page(config, group_id, since, offset).await?;
+        let results = page.results.unwrap_or_default();
+        if results.is_empty() {
+            break;
+        }
+
+        has_next = page.has_next.unwrap_or(false);
+        offset += results.len();
+        entries.extend(results);
+    }
+
+    entries.sort_by_key(created_at);
+    let truncated = skipped || has_next || entries.len() > limit;
+    entries.truncate(limit);
+
+    Ok((entries, truncated))
+}
+
+/// Returns whether there are entries left over for the next poll.
+async fn poll(config: &Configuration, own_user_id: &str) -> Result<bool> {
+    let group_id = CONFIG
+        .group_id
+        .clone()
+        .context("group_id config variable is not set")?;
+
+    let mut state: Cursor = storage::load(CURSOR_FILE);
+
+    let Some(cursor) = state.cursor else {
+        // Start from now rather than replaying the group's whole history.
+        state.cursor = Some(Utc::now());
+        storage::save(CURSOR_FILE, &state)?;
+        return Ok(false);
+    };
+
+    let (entries, truncated) = fetch_since(config, &group_id, cursor).await?;
+    if truncated {
+        warn!(
+            "More than {} new group audit log entries, catching up over the next polls",
+            entries.len()
+        );
+    }
+
+    // Only the fetched entries, so the cursor never moves past any that weren't processed.
+    let mut activities = entries
+        .into_iter()
+        .filter_map(to_activity)
+        .filter(|activity| activity.created_at >= cursor && !state.seen.contains(&activity.id))
+        .collect::<Vec<_>>();
+    activities.sort_by_key(|activity| activity.created_at);
+
+    let Some(latest) = activities.last().map(|activity| activity.created_at) else {
+        return Ok(truncated);
+    };
+
+    if latest > cursor {
+        state.seen.clear();
+    }
+    state.seen.extend(
+        activities
+            .iter()
+            .filter(|activity| activity.created_at == latest)
+            .map(|activity| activity.id.clone()),
+    );
+    state.cursor = Some(latest);
+
+    // The manager runs on a moderator's own account, so an entry by that account is only
+    // ours when it matches an action we recorded. Anything else they did in the VRChat UI.
+    for activity in &mut activities {
+        if activity.actor_id.as_deref() == Some(own_user_id)
+            && let Some(action) = activity.action()
+        {
+            activity.by_manager = audit::claim_own(&action, activity.created_at).await;
+        }
+    }
+
+    // Saved before publishing, so a crash can't deliver the same entries twice.
+    storage::save(CURSOR_FILE, &state)?;
+
+    for activity in activities {
+        if let Some(event) = activity.into_event() {
+            EVENT_BUS.publish(event).await;
+        }
+    }
+
+    Ok(truncated)
+}
+
+pub fn init(auth_config: &Configuration) {
+    let auth_config = auth_config.clone();
+    let interval = Duration::from_secs(CONFIG.group_audit.poll_interval.max(60));
+
+    tokio::spawn(async move {
+        let own_user_id = match presence::local_user(&auth_config).await {
+            Ok(id) => id,
+            Err(e) => {
+                error!("Not polling the group audit log: {:#}", e);
+                return;
+            }
+        };
+
+        loop {
+            let behind = match poll(&auth_config, own_user_id).await {
+                Ok(behind) => behind,
+                Err(e) => {
+                    error!("Failed to poll the group audit log: {:#}", e);
+                    false
+                }
+            };
+
+            tokio::time::sleep(if behind { CATCH_UP_DELAY } else { interval }).await;
+        }
+    });
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+use crate::config::CONFIG;
+use crate::events::{AppEvent, EVENT_BUS};
+use crate::storage;
+
+const STATE_FILE: &str = "invite_quota.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Hourly,
+    Daily,
+    Session,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Limit::Hourly => "hourly",
+            Limit::Daily => "daily",
+            Limit::Session => "per-session",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct QuotaState {
+    sent: Vec<DateTime<Utc>>,
+    session: Option<String>,
+    session_sent: u32,
+    #[serde(skip)]
+    reached: Option<Limit>,
+}
+
+impl QuotaState {
+    fn prune(&mut self) {
+        let cutoff = Utc::now() - Duration::days(1);
+        self.sent.retain(|sent| *sent > cutoff);
+    }
+
+    fn exceeded(&self) -> Option<Limit> {
+        let quota = &CONFIG.auto_invite.quota;
+        let hour_ago = Utc::now() - Duration::hours(1);
+
+        if quota.per_session > 0 && self.session_sent >= quota.per_session {
+            return Some(Limit::Session);
+        }
+
+        if quota.per_day > 0 && self.sent.len() as u32 >= quota.per_day {
+            return Some(Limit::Daily);
+        }
+
+        let last_hour = self.sent.iter().filter(|sent| **sent > hour_ago).count() as u32;
+        if quota.per_hour > 0 && last_hour >= quota.per_hour {
+            return Some(Limit::Hourly);
+        }
+
+        None
+    }
+
+    fn persist(&self) {
+        if let Err(e) = storage::save(STATE_FILE, self) {
+            error!("Failed to save invite quota state: {:#}", e);
+        }
+    }
+}
+
+static STATE: Lazy<Mutex<QuotaState>> = Lazy::new(|| Mutex::new(storage::load(STATE_FILE)));
+
+/// Returns `true` if another invite may be sent right now. The first time a
+/// limit is hit it is logged and published as `OnInviteQuotaReached`.
+pub async fn available() -> bool {
+    let mut state = STATE.lock().await;
+    state.prune();
+
+    let exceeded = state.exceeded();
+    if let Some(limit) = exceeded
+        && state.reached != exceeded
+    {
+        warn!("Invite quota reached ({} limit)", limit);
+        EVENT_BUS
+            .publish(AppEvent::OnInviteQuotaReached(limit.to_string()))
+            .await;
+    }
+
+    state.reached = exceeded;
+    exceeded.is_none()
+}
+
+pub async fn record() {
+    let mut state = STATE.lock().await;
+    state.sent.push(Utc::now());
+    state.session_sent += 1;
+    state.persist();
+}
+
+pub async fn start_session(location: Option<String>) {
+    let mut state = STATE.lock().await;
+
+    // Restarting inside the same instance keeps the session counter.
+    if state.session != location {
+        state.session = location;
+        state.session_sent = 0;
+        state.reached = None;
+        state.persist();
+    }
+}
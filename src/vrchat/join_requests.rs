@@ -0,0 +1,185 @@
+use crate::config::CONFIG;
+use crate::events::{AppEvent, EVENT_BUS, UserSnapshot};
+use crate::storage;
+use crate::vrchat::{automation, criteria};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{error, info};
+use vrchatapi::apis;
+use vrchatapi::apis::configuration::Configuration;
+use vrchatapi::models::{GroupJoinRequestAction, RespondGroupJoinRequest, User};
+
+const ESCALATED_FILE: &str = "escalated_join_requests.json";
+const PAGE_SIZE: i32 = 100;
+
+enum Decision {
+    Accept,
+    Reject(String),
+    Escalate(String),
+}
+
+async fn decide(config: &Configuration, user: &User) -> Result<Decision> {
+    let settings = &CONFIG.join_requests;
+
+    if let Some(rejection) = criteria::evaluate(config, &settings.reject, user).await? {
+        return Ok(Decision::Reject(rejection.to_string()));
+    }
+
+    // Accepting everyone has to be configured on purpose, not fallen into.
+    if settings.accept.is_empty() {
+        return Ok(Decision::Escalate(
+            "no accept criteria are configured".to_string(),
+        ));
+    }
+
+    match criteria::evaluate(config, &settings.accept, user).await? {
+        Some(rejection) => Ok(Decision::Escalate(rejection.to_string())),
+        None => Ok(Decision::Accept),
+    }
+}
+
+async fn respond(
+    config: &Configuration,
+    group_id: &str,
+    user_id: &str,
+    action: GroupJoinRequestAction,
+) -> Result<()> {
+    let mut request = RespondGroupJoinRequest::new(action);
+    if action == GroupJoinRequestAction::Reject && CONFIG.join_requests.block_rejected {
+        request.block = Some(true);
+    }
+
+    apis::groups_api::respond_group_join_request(config, group_id, user_id, request)
+        .await
+        .with_context(|| format!("Failed to {} join request", action))
+}
+
+async fn process_request(
+    config: &Configuration,
+    group_id: &str,
+    user_id: String,
+    escalated: &mut HashSet<String>,
+) -> Result<()> {
+    let user = apis::users_api::get_user(config, &user_id)
+        .await
+        .context("Failed to fetch user")?;
+
+    match decide(config, &user).await? {
+        Decision::Accept => {
+            respond(config, group_id, &user_id, GroupJoinRequestAction::Accept).await?;
+            info!(
+                "Accepted join request from {} ({})",
+                user.display_name, user_id
+            );
+
+            EVENT_BUS
+                .publish(AppEvent::OnJoinRequestAccepted(UserSnapshot::new(user)))
+                .await;
+        }
+        Decision::Reject(reason) => {
+            respond(config, group_id, &user_id, GroupJoinRequestAction::Reject).await?;
+            info!(
+                "Rejected join request from {} ({}): {}",
+                user.display_name, user_id, reason
+            );
+
+            EVENT_BUS
+                .publish(AppEvent::OnJoinRequestRejected(
+                    UserSnapshot::new(user),
+                    reason,
+                ))
+                .await;
+        }
+        Decision::Escalate(reason) => {
+            if escalated.insert(user_id.clone()) {
+                info!(
+                    "Escalated join request from {} ({}): {}",
+                    user.display_name, user_id, reason
+                );
+
+                EVENT_BUS
+                    .publish(AppEvent::OnJoinRequestEscalated(
+                        UserSnapshot::new(user),
+                        reason,
+                    ))
+                    .await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn fetch_pending_requests(config: &Configuration, group_id: &str) -> Result<Vec<String>> {
+    let mut user_ids = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let page = apis::groups_api::get_group_requests(
+            config,
+            group_id,
+            Some(PAGE_SIZE),
+            Some(offset),
+            Some(false),
+        )
+        .await
+        .context("Failed to fetch group join requests")?;
+
+        let page_len = page.len() as i32;
+        user_ids.extend(page.into_iter().filter_map(|member| member.user_id));
+
+        if page_len < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+
+    Ok(user_ids)
+}
+
+async fn poll(config: &Configuration) -> Result<()> {
+    let group_id = CONFIG
+        .group_id
+        .clone()
+        .context("group_id config variable is not set")?;
+
+    if automation::is_paused("join_requests").await {
+        return Ok(());
+    }
+
+    let pending = fetch_pending_requests(config, &group_id).await?;
+
+    // Requests handled by a moderator in the meantime don't need to be remembered.
+    let mut escalated: HashSet<String> = storage::load(ESCALATED_FILE);
+    escalated.retain(|user_id| pending.contains(user_id));
+
+    for user_id in pending {
+        // Already waiting on a moderator, no need to look them up again.
+        if escalated.contains(&user_id) {
+            continue;
+        }
+
+        if let Err(err) = process_request(config, &group_id, user_id.clone(), &mut escalated).await
+        {
+            error!("Failed to process join request from {}: {:#}", user_id, err);
+        }
+    }
+
+    storage::save(ESCALATED_FILE, &escalated)
+}
+
+pub fn join_requests(auth_config: &Configuration) {
+    let auth_config = auth_config.clone();
+    let interval = Duration::from_secs(CONFIG.join_requests.poll_interval.max(60));
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = poll(&auth_config).await {
+                error!("Failed to poll join requests: {:#}", err);
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
@@ -1,27 +1,30 @@
 use tracing::info;
-use vrchatapi::models::User;
 
-use crate::{events::AppEvent, listen, vrchat::util::extract_avatar_file_id};
+use crate::{
+    events::{AppEvent, UserSnapshot},
+    listen,
+};
 
-async fn handle_event(user_id: String, user: User) {
-    let avatar_file_id = match extract_avatar_file_id(&user).unwrap_or(None) {
-        Some(avatar_file_id) => avatar_file_id,
-        _ => return,
+async fn handle_event(snapshot: UserSnapshot) {
+    let Some(avatar_file_id) = &snapshot.avatar_file_id else {
+        return;
     };
 
     info!(
         "Avatar File ID of {} ({}): {}",
-        user.display_name, user_id, avatar_file_id
+        snapshot.user.display_name,
+        snapshot.id(),
+        avatar_file_id
     );
 }
 
 pub fn log_avatar_id() {
     listen!(
-        AppEvent::OnPlayerJoined(user_id, user) => {
-          handle_event(user_id, user).await;
+        AppEvent::OnPlayerJoined(snapshot) => {
+          handle_event(snapshot).await;
         },
-        AppEvent::OnAvatarChanged(user_id, user) => {
-          handle_event(user_id, user).await;
+        AppEvent::OnAvatarChanged(snapshot) => {
+          handle_event(snapshot).await;
         }
     )
 }
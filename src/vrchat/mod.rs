@@ -1,11 +1,27 @@
+pub mod audit;
 pub mod auth;
 pub mod auto_ban;
 pub mod auto_invite;
+pub mod auto_roles;
+pub mod automation;
+pub mod avatar_info;
+pub mod ban_approval;
+pub mod criteria;
+pub mod encounters;
 pub mod event_enricher;
+pub mod evidence;
+pub mod group_audit;
+pub mod invite_quota;
+pub mod join_requests;
 pub mod log_avatar_id;
+pub mod pardons;
+pub mod presence;
 pub mod util;
+pub mod watchlist;
 
 pub use auth::auth;
 pub use auto_ban::auto_ban;
 pub use auto_invite::auto_invite;
+pub use auto_roles::auto_roles;
+pub use join_requests::join_requests;
 pub use log_avatar_id::log_avatar_id;
@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::config::CONFIG;
+use crate::events::AppEvent;
+use crate::listen;
+use crate::storage;
+use crate::vrchat::audit::{self, Action};
+use crate::vrchat::group_audit::GroupActivity;
+
+const PARDONS_FILE: &str = "pardons.json";
+
+/// A moderator unbanned this user, so automation leaves them alone in that avatar.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BanPardon {
+    pub user_id: String,
+    /// The blocklist entry (avatar file, avatar or author) they were banned for.
+    pub avatar_file_id: String,
+    pub actor: String,
+    pub pardoned_at: DateTime<Utc>,
+}
+
+/// A moderator set this role by hand, so automation no longer grants or revokes it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RolePardon {
+    pub user_id: String,
+    pub role_id: String,
+    pub actor: String,
+    pub pardoned_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct State {
+    bans: Vec<BanPardon>,
+    roles: Vec<RolePardon>,
+}
+
+impl State {
+    fn persist(&self) {
+        if let Err(e) = storage::save(PARDONS_FILE, self) {
+            error!("Failed to save pardons: {:#}", e);
+        }
+    }
+}
+
+static STATE: Lazy<Mutex<State>> = Lazy::new(|| Mutex::new(storage::load(PARDONS_FILE)));
+
+/// Pardons only count while the feature is enabled, so turning it off reverts to plain automation.
+pub async fn is_ban_pardoned(user_id: &str, entry: &str) -> bool {
+    CONFIG.pardons.enabled
+        && STATE
+            .lock()
+            .await
+            .bans
+            .iter()
+            .any(|pardon| pardon.user_id == user_id && pardon.avatar_file_id == entry)
+}
+
+pub async fn is_role_pardoned(user_id: &str, role_id: &str) -> bool {
+    CONFIG.pardons.enabled
+        && STATE
+            .lock()
+            .await
+            .roles
+            .iter()
+            .any(|pardon| pardon.user_id == user_id && pardon.role_id == role_id)
+}
+
+async fn pardon_unban(user_id: &str, actor: &str) {
+    // Only the entry of the last automatic ban is pardoned, others still get them banned.
+    let Some(avatar_file_id) = audit::last_ban_rule(user_id)
+        .await
+        .and_then(|rule| rule.rsplit_once(':').map(|(_, entry)| entry.to_string()))
+    else {
+        info!(
+            "{} unbanned {}, who wasn't banned automatically, nothing to pardon",
+            actor, user_id
+        );
+        return;
+    };
+
+    info!(
+        "{} unbanned {}, not banning them again for {}",
+        actor, user_id, avatar_file_id
+    );
+
+    let mut state = STATE.lock().await;
+    state.bans.push(BanPardon {
+        user_id: user_id.to_string(),
+        avatar_file_id,
+        actor: actor.to_string(),
+        pardoned_at: Utc::now(),
+    });
+    state.persist();
+}
+
+async fn pardon_role(user_id: &str, role_id: &str, actor: &str) {
+    let mut state = STATE.lock().await;
+
+    if state
+        .roles
+        .iter()
+        .any(|pardon| pardon.user_id == user_id && pardon.role_id == role_id)
+    {
+        return;
+    }
+
+    info!(
+        "{} changed role {} of {} by hand, automation will leave it alone",
+        actor, role_id, user_id
+    );
+
+    state.roles.push(RolePardon {
+        user_id: user_id.to_string(),
+        role_id: role_id.to_string(),
+        actor: actor.to_string(),
+        pardoned_at: Utc::now(),
+    });
+    state.persist();
+}
+
+/// A fresh manual ban means the earlier pardon no longer stands.
+async fn revoke_ban_pardons(user_id: &str) {
+    let mut state = STATE.lock().await;
+    let before = state.bans.len();
+    state.bans.retain(|pardon| pardon.user_id != user_id);
+
+    if state.bans.len() != before {
+        state.persist();
+    }
+}
+
+/// Records the effect of a manual action taken through the manager itself.
+pub async fn record_manual(action: &Action, actor: &str) {
+    if !CONFIG.pardons.enabled {
+        return;
+    }
+
+    match action {
+        Action::Ban { user_id } => revoke_ban_pardons(user_id).await,
+        Action::Unban { user_id } => pardon_unban(user_id, actor).await,
+        Action::GrantRole { user_id, role_id } | Action::RevokeRole { user_id, role_id } => {
+            pardon_role(user_id, role_id, actor).await
+        }
+        Action::Invite { .. } | Action::RevokeInvite { .. } => (),
+    }
+}
+
+/// Staff decisions made in the VRChat group UI, as seen in the group audit log.
+async fn handle_activity(activity: GroupActivity) {
+    // Our own actions are automation or already went through `record_manual`.
+    if activity.by_manager {
+        return;
+    }
+
+    let Some(user_id) = &activity.target_id else {
+        return;
+    };
+
+    match activity.event_name() {
+        "group_user_banned" => revoke_ban_pardons(user_id).await,
+        "group_user_unbanned" => pardon_unban(user_id, activity.actor()).await,
+        "group_role_assigned" | "group_role_unassigned" => {
+            if let Some(role_id) = activity.role_id() {
+                pardon_role(user_id, &role_id, activity.actor()).await;
+            }
+        }
+        _ => (),
+    }
+}
+
+pub fn init() {
+    listen!(
+        AppEvent::OnGroupUserBanned(activity)
+        | AppEvent::OnGroupUserUnbanned(activity)
+        | AppEvent::OnGroupRoleAssigned(activity)
+        | AppEvent::OnGroupRoleUnassigned(activity) => {
+            handle_activity(activity).await;
+        }
+    );
+}
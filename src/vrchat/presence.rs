@@ -0,0 +1,69 @@
+use anyhow::{Result, anyhow};
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::HashSet;
+use tokio::sync::Mutex;
+use vrchatapi::apis;
+use vrchatapi::apis::configuration::Configuration;
+use vrchatapi::models::EitherUserOrTwoFactor;
+
+use crate::{events::AppEvent, listen};
+
+#[derive(Default)]
+struct Presence {
+    location: Option<String>,
+    players: HashSet<String>,
+}
+
+static PRESENCE: Lazy<Mutex<Presence>> = Lazy::new(|| Mutex::new(Presence::default()));
+static LOCAL_USER: OnceCell<String> = OnceCell::new();
+
+/// Remembers who we are logged in as, the log reports our own joins like anyone else's.
+pub async fn learn_local_user(config: &Configuration) -> Result<()> {
+    local_user(config).await.map(|_| ())
+}
+
+/// The logged in user's ID, only looked up the first time it's needed.
+pub async fn local_user(config: &Configuration) -> Result<&'static str> {
+    if let Some(id) = LOCAL_USER.get() {
+        return Ok(id);
+    }
+
+    match apis::authentication_api::get_current_user(config).await {
+        Ok(EitherUserOrTwoFactor::CurrentUser(user)) => Ok(LOCAL_USER.get_or_init(|| user.id)),
+        _ => Err(anyhow!("Failed to get current user")),
+    }
+}
+
+/// Whether `user_id` is the logged in user. Always false before login.
+pub fn is_local_user(user_id: &str) -> bool {
+    LOCAL_USER.get().is_some_and(|id| id == user_id)
+}
+
+pub async fn is_present(user_id: &str) -> bool {
+    PRESENCE.lock().await.players.contains(user_id)
+}
+
+pub async fn current_location() -> Option<String> {
+    PRESENCE.lock().await.location.clone()
+}
+
+pub fn init() {
+    listen!(
+        AppEvent::OnPlayerJoinedRaw(user_id) => {
+            PRESENCE.lock().await.players.insert(user_id);
+        },
+        AppEvent::OnPlayerLeftRaw(user_id) => {
+            PRESENCE.lock().await.players.remove(&user_id);
+        },
+        AppEvent::OnInstanceJoined(location) => {
+            let mut presence = PRESENCE.lock().await;
+            presence.location = Some(location);
+            presence.players.clear();
+        },
+        AppEvent::OnInstanceLeft => {
+            let mut presence = PRESENCE.lock().await;
+            presence.location = None;
+            presence.players.clear();
+        }
+    );
+}
@@ -1,20 +1,45 @@
 use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
 use url::Url;
 use vrchatapi::models::User;
 
 pub fn extract_avatar_file_id(user: &User) -> Result<Option<String>> {
-    if user.profile_pic_override != "".to_string() {
+    if !user.profile_pic_override.is_empty() {
         return Ok(None);
     }
 
-    let avatar_file_id = Url::parse(&user.current_avatar_image_url)
+    file_id_from_url(&user.current_avatar_image_url).map(Some)
+}
+
+/// The `file_` ID in a VRChat file URL like `https://api.vrchat.cloud/api/1/file/file_.../1/file`.
+pub fn file_id_from_url(url: &str) -> Result<String> {
+    Url::parse(url)
         .ok()
         .context("Failed to parse avatar URL")?
         .path_segments()
         .context("Failed to get path segments")?
         .nth(3)
         .map(|s| s.to_string())
-        .context("Failed to extract avatar ID")?;
+        .context("Failed to extract avatar ID")
+}
 
-    Ok(Some(avatar_file_id))
+/// Reads a list file, replacing invalid UTF-8 rather than failing on it.
+pub fn read_lossy(path: &str) -> io::Result<String> {
+    Ok(String::from_utf8_lossy(&fs::read(path)?).into_owned())
+}
+
+/// Appends `line` to a one-entry-per-line list file, creating the file if needed.
+pub fn append_line(path: &str, line: &str) -> io::Result<()> {
+    let needs_newline = match fs::read(path) {
+        Ok(contents) => contents.last().is_some_and(|byte| *byte != b'\n'),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+        Err(e) => return Err(e),
+    };
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if needs_newline {
+        writeln!(file)?;
+    }
+    writeln!(file, "{line}")
 }
@@ -0,0 +1,61 @@
+use crate::config::CONFIG;
+use crate::events::{AppEvent, EVENT_BUS, UserSnapshot};
+use crate::listen;
+use crate::vrchat::util;
+use std::collections::HashSet;
+use std::io;
+use tracing::{error, info};
+
+fn watchlist_file() -> String {
+    CONFIG
+        .watchlist_file
+        .clone()
+        .unwrap_or("watchlist.txt".to_string())
+}
+
+pub fn load_watchlist() -> io::Result<HashSet<String>> {
+    let contents = match util::read_lossy(&watchlist_file()) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(contents
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// Adds a user to the watchlist. Returns false if they were already on it.
+pub fn add(user_id: &str) -> io::Result<bool> {
+    if load_watchlist()?.contains(user_id) {
+        return Ok(false);
+    }
+
+    util::append_line(&watchlist_file(), user_id)?;
+    Ok(true)
+}
+
+async fn check_user(snapshot: UserSnapshot) {
+    match load_watchlist() {
+        Ok(watchlist) if watchlist.contains(snapshot.id()) => {
+            info!(
+                "Watchlisted user {} ({}) joined",
+                snapshot.user.display_name,
+                snapshot.id()
+            );
+            EVENT_BUS.publish(AppEvent::OnWatchlistHit(snapshot)).await;
+        }
+        Ok(_) => (),
+        Err(e) => error!("Failed to load watchlist: {:#}", e),
+    }
+}
+
+pub fn init() {
+    listen!(
+        AppEvent::OnPlayerJoined(snapshot) => {
+            check_user(snapshot).await;
+        }
+    );
+}